@@ -1,8 +1,12 @@
+use std::collections::HashMap;
 use std::io::{self, Read};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 
 use clap::{Args, Parser, Subcommand};
 
+use crate::config::Config;
+use crate::notify::ClientNotifier;
 use crate::service::{OracleRequest, OracleService};
 
 #[derive(Parser)]
@@ -10,14 +14,38 @@ use crate::service::{OracleRequest, OracleService};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Command>,
+
+    /// Log filter such as `info` or `oracle=debug` (overrides ORACLE_LOG)
+    #[arg(long = "log-level", value_name = "FILTER", global = true)]
+    pub log_level: Option<String>,
+
+    /// Append logs to this file instead of stderr
+    #[arg(long = "log-file", value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 pub enum Command {
     /// Run a one-off Oracle request from the command line
-    Call(CallArgs),
+    Call(Box<CallArgs>),
     /// Start the Oracle MCP server over stdio (default)
-    Serve,
+    Serve(ServeArgs),
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Serve a plain REST/JSON API instead of MCP over stdio
+    #[arg(long)]
+    pub rest: bool,
+
+    /// Address the REST API listens on
+    #[arg(
+        long,
+        value_name = "ADDR",
+        default_value = "127.0.0.1:8787",
+        requires = "rest"
+    )]
+    pub bind: SocketAddr,
 }
 
 #[derive(Args)]
@@ -34,9 +62,68 @@ pub struct CallArgs {
     #[arg(long = "extra", value_name = "TEXT")]
     pub extra_context: Option<String>,
 
-    /// File paths to include as context (repeatable)
+    /// Files, directories or globs to include as context (repeatable). Use 'path:120-200' or
+    /// 'path:88±20' for line ranges, 'path#Symbol' for one definition, 'path@REV' to read a git
+    /// revision and a '!' prefix to exclude
     #[arg(short = 'f', long = "file", value_name = "PATH")]
     pub files: Vec<PathBuf>,
+
+    /// Prepend an outline of the repository (directory tree and top-level signatures)
+    #[arg(long = "repo-map")]
+    pub repo_map: bool,
+
+    /// Add the workspace files most relevant to the problem text (local BM25 index)
+    #[arg(long = "auto-context")]
+    pub auto_context: bool,
+
+    /// Compiler or linter output to analyze: cargo/rustc JSON, ESLint JSON or tsc (use '-' for stdin)
+    #[arg(long = "diagnostics", value_name = "PATH")]
+    pub diagnostics: Option<PathBuf>,
+
+    /// Run a command and include its exit code and output as context (repeatable), e.g.
+    /// --exec "cargo test -p core". Split like a shell would but not run through one
+    #[arg(long = "exec", value_name = "CMD")]
+    pub exec: Vec<String>,
+
+    /// Include the diff of uncommitted changes against HEAD and the last few commits
+    #[arg(long = "git-diff")]
+    pub git_diff: bool,
+
+    /// Include the diff of changes staged in the index
+    #[arg(long = "staged")]
+    pub staged: bool,
+
+    /// Include the diff and commit log of a range, e.g. 'main..HEAD'
+    #[arg(long = "range", value_name = "RANGE")]
+    pub range: Option<String>,
+
+    /// Also include the full post-change contents of files touched by the git changes
+    #[arg(long = "changed-files")]
+    pub changed_files: bool,
+
+    /// When the files don't fit the prompt, summarize the overflow with the cheaper
+    /// '[summarize] model' instead of cutting it
+    #[arg(long = "summarize-overflow")]
+    pub summarize_overflow: bool,
+
+    /// Screenshot or diagram to attach (repeatable): PNG, JPEG, WebP or GIF. Image files passed
+    /// with --file are attached too
+    #[arg(long = "image", value_name = "PATH")]
+    pub images: Vec<PathBuf>,
+
+    /// Spec or datasheet to include (repeatable): PDF, Word (.docx) or Markdown. PDF and .docx
+    /// files passed with --file are included the same way
+    #[arg(long = "doc", value_name = "PATH")]
+    pub documents: Vec<PathBuf>,
+
+    /// Named workspace root (repeatable), e.g. --root client=../web; entries prefixed with
+    /// 'client:' resolve against it
+    #[arg(long = "root", value_name = "NAME=PATH")]
+    pub roots: Vec<String>,
+
+    /// Send the prompt without replacing secrets with placeholders
+    #[arg(long = "no-redact")]
+    pub no_redact: bool,
 }
 
 #[derive(Debug)]
@@ -56,35 +143,87 @@ impl std::fmt::Display for CliError {
 
 impl std::error::Error for CliError {}
 
-pub async fn run_cli_call(args: CallArgs) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn run_cli_call(
+    args: CallArgs,
+    config: Config,
+) -> Result<(), Box<dyn std::error::Error>> {
     let CallArgs {
         problem,
         problem_file,
         extra_context,
         files,
+        repo_map,
+        auto_context,
+        diagnostics,
+        exec,
+        git_diff,
+        staged,
+        range,
+        changed_files,
+        summarize_overflow,
+        images,
+        documents,
+        roots,
+        no_redact,
     } = args;
 
+    if diagnostics.as_deref() == Some(Path::new("-"))
+        && problem_file.as_deref() == Some(Path::new("-"))
+    {
+        return Err(Box::new(CliError::new(
+            "Only one of --problem-file and --diagnostics can read from stdin",
+        )));
+    }
+
+    let mut named_roots = HashMap::new();
+    for root in roots {
+        let Some((name, dir)) = root.split_once('=') else {
+            return Err(Box::new(CliError::new(format!(
+                "Invalid --root `{root}`: expected NAME=PATH"
+            ))));
+        };
+        named_roots.insert(name.to_string(), dir.to_string());
+    }
+
     let problem_text = load_problem_text(problem, problem_file).await?;
-    let files = if files.is_empty() {
-        None
-    } else {
-        Some(
-            files
+    let paths = |paths: Vec<PathBuf>| {
+        (!paths.is_empty()).then(|| {
+            paths
                 .into_iter()
                 .map(|path| path.to_string_lossy().into_owned())
-                .collect(),
-        )
+                .collect()
+        })
     };
 
     let request = OracleRequest {
         problem: problem_text,
-        files,
+        files: paths(files),
         extra_context,
+        repo_map: repo_map.then_some(true),
+        auto_context: auto_context.then_some(true),
+        diagnostics: match diagnostics {
+            Some(path) => Some(load_diagnostics(&path).await?),
+            None => None,
+        },
+        commands: (!exec.is_empty()).then_some(exec),
+        git_diff: git_diff.then_some(true),
+        git_staged: staged.then_some(true),
+        git_range: range,
+        include_changed_files: changed_files.then_some(true),
+        summarize_overflow: summarize_overflow.then_some(true),
+        images: paths(images),
+        documents: paths(documents),
+        roots: (!named_roots.is_empty()).then_some(named_roots),
     };
 
-    let service = OracleService::new();
+    let mut service = OracleService::new(config)
+        .allow_any_command()
+        .trust_named_roots();
+    if no_redact {
+        service = service.skip_redaction();
+    }
     let answer = service
-        .call_openai(request)
+        .call_openai(request, &[], &ClientNotifier::default())
         .await
         .map_err(|err| CliError::new(format!("Oracle encountered an error: {}", err.message)))?;
 
@@ -92,6 +231,15 @@ pub async fn run_cli_call(args: CallArgs) -> Result<(), Box<dyn std::error::Erro
     Ok(())
 }
 
+async fn load_diagnostics(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    if path == Path::new("-") {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+        return Ok(buffer);
+    }
+    Ok(tokio::fs::read_to_string(path).await?)
+}
+
 async fn load_problem_text(
     inline: Option<String>,
     problem_file: Option<PathBuf>,
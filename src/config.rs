@@ -0,0 +1,314 @@
+//! User configuration, read from `ORACLE_CONFIG` or `~/.config/oracle/config.toml`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::context::sandbox::DEFAULT_DENY;
+use crate::tokens::Tokenizer;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// OpenAI model that answers requests.
+    pub model: String,
+    pub exec: ExecConfig,
+    pub summarize: SummarizeConfig,
+    pub documents: DocumentsConfig,
+    pub sandbox: SandboxConfig,
+    pub redact: RedactConfig,
+    /// `[models."NAME"]`: budget overrides per model, on top of the built-in defaults.
+    pub models: HashMap<String, ModelConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model: "gpt-5-pro".to_string(),
+            exec: ExecConfig::default(),
+            summarize: SummarizeConfig::default(),
+            documents: DocumentsConfig::default(),
+            sandbox: SandboxConfig::default(),
+            redact: RedactConfig::default(),
+            models: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Token budget for `model`: built-in defaults for its family, overridden by `[models."NAME"]`.
+    pub fn budget(&self, model: &str) -> ModelBudget {
+        let defaults = ModelBudget::defaults(model);
+        let Some(config) = self.models.get(model) else {
+            return defaults;
+        };
+        ModelBudget {
+            context_window: config.context_window.unwrap_or(defaults.context_window),
+            max_output_tokens: config
+                .max_output_tokens
+                .unwrap_or(defaults.max_output_tokens),
+            reasoning_tokens: config.reasoning_tokens.unwrap_or(defaults.reasoning_tokens),
+            tokenizer: config.tokenizer.unwrap_or(defaults.tokenizer),
+        }
+    }
+}
+
+/// `[models."NAME"]`: any field left out keeps the built-in default for that model.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    /// Total tokens the model accepts, prompt and output together.
+    pub context_window: Option<usize>,
+    /// Ceiling for `max_output_tokens` when a response is retried after running out of room.
+    pub max_output_tokens: Option<u32>,
+    /// Room kept free for reasoning tokens on top of `max_output_tokens`.
+    pub reasoning_tokens: Option<usize>,
+    /// `o200k`, `cl100k` or `approx`.
+    pub tokenizer: Option<Tokenizer>,
+}
+
+/// Resolved token limits for one model.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelBudget {
+    pub context_window: usize,
+    pub max_output_tokens: u32,
+    pub reasoning_tokens: usize,
+    pub tokenizer: Tokenizer,
+}
+
+impl ModelBudget {
+    fn defaults(model: &str) -> Self {
+        let (context_window, reasoning_tokens) = if model.starts_with("gpt-5") {
+            // 400k in total, of which at most 272k may be input.
+            (400_000, 120_000)
+        } else if model.starts_with("gpt-4.1") {
+            (1_047_576, 0)
+        } else if ["o1", "o3", "o4"]
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+        {
+            (200_000, 32_768)
+        } else {
+            (128_000, 0)
+        };
+        Self {
+            context_window,
+            max_output_tokens: 8_192,
+            reasoning_tokens,
+            tokenizer: Tokenizer::for_model(model),
+        }
+    }
+
+    /// Tokens left for the prompt (instructions included) once output and reasoning are reserved.
+    pub fn input_tokens(&self) -> usize {
+        self.context_window
+            .saturating_sub(self.max_output_tokens as usize)
+            .saturating_sub(self.reasoning_tokens)
+    }
+}
+
+/// `[exec]`: commands whose output can be captured as prompt context.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecConfig {
+    /// Commands MCP and REST clients may run through the `commands` field. An entry matches a
+    /// command with exactly the same arguments; a trailing `*` (`"cargo test *"`) also allows any
+    /// further arguments. Commands passed with `oracle call --exec` are not restricted.
+    pub allow: Vec<String>,
+    /// Commands still running after this many seconds are killed.
+    pub timeout_secs: u64,
+    /// Stdout and stderr are each cut to their last this many bytes.
+    pub max_output_bytes: usize,
+}
+
+impl Default for ExecConfig {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            timeout_secs: 120,
+            max_output_bytes: 16 * 1024,
+        }
+    }
+}
+
+impl ExecConfig {
+    /// Whether `command` (already split into arguments) matches an `allow` entry.
+    pub fn allows(&self, argv: &[String]) -> bool {
+        self.allow.iter().any(|entry| {
+            let Some(pattern) = shlex::split(entry) else {
+                return false;
+            };
+            match pattern.split_last() {
+                Some((last, prefix)) if last == "*" => argv.starts_with(prefix),
+                _ => argv == pattern.as_slice(),
+            }
+        })
+    }
+}
+
+/// `[summarize]`: condensing project files that don't fit the prompt when a request sets
+/// `summarize_overflow`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummarizeConfig {
+    /// Cheaper model that writes the summaries.
+    pub model: String,
+    /// Rough length asked of each summary, and the room reserved for it in the final prompt.
+    pub summary_tokens: usize,
+    /// Files beyond this many are cut or dropped as usual instead of summarized.
+    pub max_files: usize,
+    /// Summary requests in flight at once.
+    pub concurrency: usize,
+}
+
+impl Default for SummarizeConfig {
+    fn default() -> Self {
+        Self {
+            model: "gpt-5-mini".to_string(),
+            summary_tokens: 1_024,
+            max_files: 16,
+            concurrency: 4,
+        }
+    }
+}
+
+/// `[documents]`: how PDF attachments reach the model.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DocumentsConfig {
+    /// Upload PDFs through the Files API and attach them as `input_file` parts, so the model also
+    /// sees figures and layout. Turn off for endpoints without file inputs; the text is then
+    /// extracted locally.
+    pub upload: bool,
+}
+
+impl Default for DocumentsConfig {
+    fn default() -> Self {
+        Self { upload: true }
+    }
+}
+
+/// `[sandbox]`: which files may be read into a prompt. The workspace (the MCP client's roots,
+/// or the working directory) is always readable.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SandboxConfig {
+    /// More directories files may be read from. A leading `~/` is the home directory; relative
+    /// paths are relative to the working directory.
+    pub allow: Vec<String>,
+    /// Globs for files that are never read, even inside a readable directory. A pattern without
+    /// `/` matches any path component (`.env*`, `id_*`); one with `/` matches the path from the
+    /// root of its readable directory. Setting this replaces the default list.
+    pub deny: Vec<String>,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: DEFAULT_DENY
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// The `allow` directories as paths, with `~/` expanded.
+    pub fn allowed_dirs(&self) -> Vec<PathBuf> {
+        self.allow
+            .iter()
+            .map(|dir| match (dir.strip_prefix("~/"), env::var_os("HOME")) {
+                (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+                _ => PathBuf::from(dir),
+            })
+            .collect()
+    }
+}
+
+/// `[redact]`: replacing secrets in the prompt with placeholders before it is sent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Scan the prompt at all. `oracle call --no-redact` turns it off for one request.
+    pub enabled: bool,
+    /// Built-in rules to skip, by name (`high-entropy`, `jwt`, ...).
+    pub disable: Vec<String>,
+    /// `[[redact.rules]]`: extra rules.
+    pub rules: Vec<RedactRule>,
+    /// Bits of entropy per character from which a long token mixing letters and digits counts
+    /// as a secret.
+    pub entropy_threshold: f64,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            disable: Vec::new(),
+            rules: Vec::new(),
+            entropy_threshold: 3.5,
+        }
+    }
+}
+
+/// A custom redaction rule. With a capture group only the group is replaced.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedactRule {
+    /// Shown in the placeholder: `[REDACTED:NAME:1]`.
+    pub name: String,
+    /// Regular expression in Rust `regex` syntax.
+    pub pattern: String,
+}
+
+/// A config file that exists but can't be read or parsed.
+#[derive(Debug)]
+pub struct ConfigError(String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Loads the configuration file. A missing file yields the defaults; a file that exists but
+/// cannot be read or parsed is an error, so a typo never silently disables a setting.
+pub fn load() -> Result<Config, ConfigError> {
+    let (path, explicit) = match env::var_os("ORACLE_CONFIG") {
+        Some(path) => (PathBuf::from(path), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if !explicit && err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Config::default());
+        }
+        Err(err) => {
+            return Err(ConfigError(format!(
+                "Failed to read config file {}: {err}",
+                path.display()
+            )));
+        }
+    };
+
+    toml::from_str(&text)
+        .map_err(|err| ConfigError(format!("Invalid config file {}: {err}", path.display())))
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("oracle").join("config.toml"))
+}
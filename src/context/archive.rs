@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+
+/// Archive members larger than this are not read.
+pub const MAX_MEMBER_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Archive formats whose members can be named in `files` (`vendor.tar.gz!/src/lib.rs`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    /// Gzip-compressed tar, including `.tgz` and `.crate` files.
+    TarGz,
+    Tar,
+}
+
+impl ArchiveKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") || name.ends_with(".jar") || name.ends_with(".whl") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") || name.ends_with(".crate") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// A regular file inside an archive.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// Path inside the archive, without a leading `./`.
+    pub name: String,
+    pub size: u64,
+}
+
+/// Lists the regular files in an archive, sorted by name. Blocking.
+pub fn list_members(path: &Path) -> Result<Vec<Member>, String> {
+    let kind = ArchiveKind::from_path(path).ok_or_else(|| unsupported(path))?;
+    let mut members = Vec::new();
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = open_zip(path)?;
+            for index in 0..archive.len() {
+                let entry = archive
+                    .by_index_raw(index)
+                    .map_err(|err| read_error(path, err))?;
+                if entry.is_dir() {
+                    continue;
+                }
+                members.push(Member {
+                    name: normalize(entry.name()),
+                    size: entry.size(),
+                });
+            }
+        }
+        ArchiveKind::TarGz | ArchiveKind::Tar => {
+            let mut archive = open_tar(path, kind)?;
+            for entry in archive.entries().map_err(|err| read_error(path, err))? {
+                let entry = entry.map_err(|err| read_error(path, err))?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let name = entry.path().map_err(|err| read_error(path, err))?;
+                members.push(Member {
+                    name: normalize(&name.to_string_lossy()),
+                    size: entry.size(),
+                });
+            }
+        }
+    }
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(members)
+}
+
+/// Reads the named members of an archive in a single pass, refusing members over `limit` bytes.
+/// Each member gets its bytes or the reason it couldn't be read. Blocking.
+pub fn read_members(
+    path: &Path,
+    members: &HashSet<String>,
+    limit: u64,
+) -> Result<HashMap<String, Result<Vec<u8>, String>>, String> {
+    let kind = ArchiveKind::from_path(path).ok_or_else(|| unsupported(path))?;
+    let mut read = HashMap::new();
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = open_zip(path)?;
+            for index in 0..archive.len() {
+                let Some(name) = archive.name_for_index(index).map(normalize) else {
+                    continue;
+                };
+                if !members.contains(&name) || read.contains_key(&name) {
+                    continue;
+                }
+                let bytes = match archive.by_index(index) {
+                    Ok(entry) if entry.is_dir() => continue,
+                    Ok(entry) => {
+                        let declared = entry.size();
+                        read_bounded(path, entry, declared, limit)
+                    }
+                    Err(err) => Err(read_error(path, err)),
+                };
+                read.insert(name, bytes);
+            }
+        }
+        ArchiveKind::TarGz | ArchiveKind::Tar => {
+            let mut archive = open_tar(path, kind)?;
+            for entry in archive.entries().map_err(|err| read_error(path, err))? {
+                let entry = entry.map_err(|err| read_error(path, err))?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let name = normalize(
+                    &entry
+                        .path()
+                        .map_err(|err| read_error(path, err))?
+                        .to_string_lossy(),
+                );
+                if !members.contains(&name) || read.contains_key(&name) {
+                    continue;
+                }
+                let declared = entry.size();
+                read.insert(name, read_bounded(path, entry, declared, limit));
+                // A compressed tar can't seek, so stop decompressing once everything is read.
+                if read.len() == members.len() {
+                    break;
+                }
+            }
+        }
+    }
+    for member in members {
+        if !read.contains_key(member) {
+            read.insert(member.clone(), Err(not_found(path, member)));
+        }
+    }
+    Ok(read)
+}
+
+/// Reads one member of an archive, refusing members over `limit` bytes. Blocking.
+pub fn read_member(path: &Path, member: &str, limit: u64) -> Result<Vec<u8>, String> {
+    let members = HashSet::from([member.to_string()]);
+    read_members(path, &members, limit)?
+        .remove(member)
+        .unwrap_or_else(|| Err(not_found(path, member)))
+}
+
+/// Reads an entry of `declared` bytes. The header can lie, so the read itself also stops past
+/// `limit`.
+fn read_bounded(
+    path: &Path,
+    entry: impl Read,
+    declared: u64,
+    limit: u64,
+) -> Result<Vec<u8>, String> {
+    let too_large =
+        |size: u64| format!("archive member is {size} bytes, over the {limit}-byte limit");
+    if declared > limit {
+        return Err(too_large(declared));
+    }
+    let mut bytes = Vec::with_capacity(declared as usize);
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|err| read_error(path, err))?;
+    if bytes.len() as u64 > limit {
+        return Err(format!(
+            "archive member is over the {limit}-byte limit despite a declared size of {declared} bytes"
+        ));
+    }
+    Ok(bytes)
+}
+
+fn open_zip(path: &Path) -> Result<zip::ZipArchive<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|err| read_error(path, err))?;
+    zip::ZipArchive::new(BufReader::new(file)).map_err(|err| read_error(path, err))
+}
+
+fn open_tar(path: &Path, kind: ArchiveKind) -> Result<tar::Archive<Box<dyn Read>>, String> {
+    let file = BufReader::new(File::open(path).map_err(|err| read_error(path, err))?);
+    let reader: Box<dyn Read> = match kind {
+        ArchiveKind::TarGz => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_start_matches("./").to_string()
+}
+
+fn unsupported(path: &Path) -> String {
+    format!(
+        "{} is not a supported archive (.zip, .jar, .whl, .tar, .tar.gz, .tgz or .crate)",
+        path.display()
+    )
+}
+
+fn not_found(path: &Path, member: &str) -> String {
+    format!("{} has no member `{member}`", path.display())
+}
+
+fn read_error(path: &Path, err: impl std::fmt::Display) -> String {
+    format!("failed to read archive {}: {err}", path.display())
+}
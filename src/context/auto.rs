@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use ignore::WalkBuilder;
+
+use super::oracleignore::Ignored;
+use super::roots::Roots;
+
+/// How many files `auto_context` adds at most.
+pub const AUTO_CONTEXT_MAX_FILES: usize = 8;
+/// Combined size budget for auto-selected files.
+pub const AUTO_CONTEXT_MAX_BYTES: u64 = 256 * 1024;
+/// Files larger than this are not indexed.
+const MAX_INDEXED_FILE_BYTES: u64 = 256 * 1024;
+/// Stop indexing after this many files so huge trees stay responsive.
+const MAX_INDEXED_FILES: usize = 20_000;
+/// Files scoring below this fraction of the best match are left out as noise.
+const MIN_RELATIVE_SCORE: f64 = 0.25;
+/// Path and file-name terms count this many times, since a match there is a strong signal.
+const PATH_TERM_WEIGHT: usize = 3;
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "can", "do", "does", "else", "for",
+    "from", "has", "have", "how", "if", "in", "into", "is", "it", "its", "let", "my", "no", "not",
+    "of", "on", "or", "our", "so", "that", "the", "then", "there", "this", "to", "use", "was",
+    "we", "what", "when", "where", "which", "while", "why", "will", "with", "you", "fn", "pub",
+    "self", "return", "def", "func", "function", "const", "var", "mut", "impl", "import",
+];
+
+/// A file picked by BM25 scoring, with the terms that contributed most to its score.
+#[derive(Debug, Clone)]
+pub struct AutoSelection {
+    pub display: String,
+    pub path: PathBuf,
+    pub score: f64,
+    pub matched_terms: Vec<String>,
+}
+
+struct Document {
+    display: String,
+    path: PathBuf,
+    size: u64,
+    length: usize,
+    term_counts: HashMap<String, usize>,
+}
+
+struct Scored<'a> {
+    score: f64,
+    /// Per-term contributions to `score`, largest first.
+    contributions: Vec<(f64, &'a str)>,
+    doc: &'a Document,
+}
+
+/// Picks the workspace files most relevant to `query` with BM25 over identifiers and paths.
+///
+/// Everything runs locally: files under the base directory (honoring `.gitignore`) are
+/// tokenized into identifiers and their camelCase/snake_case parts, then scored against the
+/// query terms. Up to [`AUTO_CONTEXT_MAX_FILES`] files within [`AUTO_CONTEXT_MAX_BYTES`] are
+/// returned, best first, skipping weak matches and any path in `exclude` (files the request
+/// already names). Files the sandbox refuses are never indexed, nor are files and directories a
+/// `.oracleignore` excludes; those are added to `ignored`. Blocking.
+pub fn select_relevant_files(
+    roots: &Roots,
+    query: &str,
+    exclude: &HashSet<PathBuf>,
+    ignored: &mut Vec<(String, Ignored)>,
+) -> Vec<AutoSelection> {
+    let query_terms: HashSet<String> = tokenize(query).into_iter().collect();
+    if query_terms.is_empty() {
+        return Vec::new();
+    }
+
+    let documents = index_workspace(roots, ignored);
+    if documents.is_empty() {
+        return Vec::new();
+    }
+
+    let doc_count = documents.len() as f64;
+    let avg_length =
+        documents.iter().map(|doc| doc.length).sum::<usize>() as f64 / doc_count.max(1.0);
+
+    let idf: HashMap<&str, f64> = query_terms
+        .iter()
+        .map(|term| {
+            let df = documents
+                .iter()
+                .filter(|doc| doc.term_counts.contains_key(term))
+                .count() as f64;
+            let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+            (term.as_str(), idf)
+        })
+        .collect();
+
+    let mut scored: Vec<Scored> = documents
+        .iter()
+        .filter(|doc| !exclude.contains(&doc.path))
+        .filter_map(|doc| {
+            let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * doc.length as f64 / avg_length);
+            let mut contributions: Vec<(f64, &str)> = idf
+                .iter()
+                .filter_map(|(term, idf)| {
+                    let tf = *doc.term_counts.get(*term)? as f64;
+                    Some((idf * tf * (BM25_K1 + 1.0) / (tf + norm), *term))
+                })
+                .collect();
+            if contributions.is_empty() {
+                return None;
+            }
+            contributions.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(b.1)));
+            let score = contributions.iter().map(|(value, _)| value).sum();
+            Some(Scored {
+                score,
+                contributions,
+                doc,
+            })
+        })
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.doc.display.cmp(&b.doc.display))
+    });
+
+    let best_score = scored.first().map_or(0.0, |scored| scored.score);
+
+    let mut selected = Vec::new();
+    let mut total_bytes = 0u64;
+    for Scored {
+        score,
+        contributions,
+        doc,
+    } in scored
+    {
+        if selected.len() >= AUTO_CONTEXT_MAX_FILES || score < best_score * MIN_RELATIVE_SCORE {
+            break;
+        }
+        if total_bytes + doc.size > AUTO_CONTEXT_MAX_BYTES {
+            continue;
+        }
+        total_bytes += doc.size;
+        selected.push(AutoSelection {
+            display: doc.display.clone(),
+            path: doc.path.clone(),
+            score,
+            matched_terms: contributions
+                .iter()
+                .take(5)
+                .map(|(_, term)| term.to_string())
+                .collect(),
+        });
+    }
+
+    selected
+}
+
+fn index_workspace(roots: &Roots, ignored: &mut Vec<(String, Ignored)>) -> Vec<Document> {
+    let base = roots.base();
+    let mut walker = WalkBuilder::new(base);
+    walker.require_git(false).sort_by_file_path(|a, b| a.cmp(b));
+    let pruned = roots.prune_ignored(&mut walker, base);
+
+    let documents = walker
+        .build()
+        .filter_map(Result::ok)
+        .filter(|dent| dent.file_type().is_some_and(|kind| kind.is_file()))
+        .take(MAX_INDEXED_FILES)
+        .filter_map(|dent| {
+            roots.check(dent.path()).ok()?;
+            let size = dent.metadata().ok()?.len();
+            if size > MAX_INDEXED_FILE_BYTES {
+                return None;
+            }
+            let bytes = std::fs::read(dent.path()).ok()?;
+            if bytes.iter().take(1024).any(|byte| *byte == 0) {
+                return None;
+            }
+            let text = String::from_utf8(bytes).ok()?;
+
+            let relative = dent.path().strip_prefix(base).unwrap_or(dent.path());
+            let display = relative.to_string_lossy().into_owned();
+
+            let mut term_counts: HashMap<String, usize> = HashMap::new();
+            let mut length = 0usize;
+            for term in tokenize(&text) {
+                *term_counts.entry(term).or_default() += 1;
+                length += 1;
+            }
+            for term in tokenize(&display) {
+                *term_counts.entry(term).or_default() += PATH_TERM_WEIGHT;
+                length += PATH_TERM_WEIGHT;
+            }
+
+            Some(Document {
+                display,
+                path: dent.path().to_path_buf(),
+                size,
+                length,
+                term_counts,
+            })
+        })
+        .collect();
+    ignored.append(&mut pruned.lock().unwrap_or_else(|err| err.into_inner()));
+    documents
+}
+
+/// Lowercased identifiers plus their camelCase and snake_case parts, minus stopwords.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        let parts = split_identifier(word);
+        if parts.len() > 1 {
+            push_term(&mut terms, word);
+        }
+        for part in parts {
+            push_term(&mut terms, part);
+        }
+    }
+    terms
+}
+
+fn push_term(terms: &mut Vec<String>, word: &str) {
+    let term = word.trim_matches('_').to_lowercase();
+    if term.chars().count() < 2
+        || term.chars().all(|c| c.is_ascii_digit())
+        || STOPWORDS.contains(&term.as_str())
+    {
+        return;
+    }
+    terms.push(term);
+}
+
+/// Splits `parse_HTTPResponse2` into `parse`, `HTTP`, `Response2`.
+fn split_identifier(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    for chunk in word.split('_').filter(|chunk| !chunk.is_empty()) {
+        let chars: Vec<(usize, char)> = chunk.char_indices().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (idx, current) = chars[i];
+            let previous = chars[i - 1].1;
+            let next_is_lower = chars.get(i + 1).is_some_and(|(_, c)| c.is_lowercase());
+            let boundary = current.is_uppercase()
+                && (previous.is_lowercase()
+                    || previous.is_ascii_digit()
+                    || (previous.is_uppercase() && next_is_lower));
+            if boundary {
+                parts.push(&chunk[start..idx]);
+                start = idx;
+            }
+        }
+        parts.push(&chunk[start..]);
+    }
+    parts
+}
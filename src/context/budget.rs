@@ -0,0 +1,343 @@
+use super::expand::FileOrigin;
+use crate::tokens::Tokenizer;
+
+const MANIFEST_HEADER: &str = "\n\n===== CUT TO FIT THE PROMPT BUDGET =====\n";
+
+/// Files that would get fewer tokens than this are dropped instead of shortened to a stub.
+const MIN_SHORTENED_TOKENS: usize = 512;
+/// Tokens set aside for the marker that ends a shortened block.
+const MARKER_TOKENS: usize = 32;
+
+impl FileOrigin {
+    /// Lower values keep their space first when the files don't fit the prompt budget: files the
+    /// request names, then excerpts the failure points at, then changed files, then guesses.
+    pub fn priority(self) -> u8 {
+        match self {
+            FileOrigin::Requested => 0,
+            FileOrigin::Diagnostic | FileOrigin::FailurePath => 1,
+            FileOrigin::Changed => 2,
+            FileOrigin::AutoSelected => 3,
+        }
+    }
+}
+
+/// A rendered file block waiting for its share of the budget.
+#[derive(Debug, Clone)]
+pub struct PendingBlock {
+    pub display: String,
+    pub priority: u8,
+    pub text: String,
+}
+
+/// What happened to a file that did not fit whole.
+#[derive(Debug, Clone)]
+pub struct Cut {
+    pub display: String,
+    pub description: String,
+}
+
+/// Fits file blocks into `available` tokens. Returns the blocks in their original order,
+/// preceded by a manifest of what was shortened or dropped, plus the cuts themselves.
+///
+/// Priorities are served in order. Within a priority the space is shared out evenly, so small
+/// files stay whole and only the largest are shortened. Shortened blocks are cut at a line
+/// boundary and end with a marker; files whose share would be too small to be useful are dropped.
+pub fn fit_blocks(
+    blocks: Vec<PendingBlock>,
+    available: usize,
+    tokenizer: Tokenizer,
+) -> (String, Vec<Cut>) {
+    let costs: Vec<usize> = blocks
+        .iter()
+        .map(|block| tokenizer.count(&block.text))
+        .collect();
+    if costs.iter().sum::<usize>() <= available {
+        return (
+            blocks.into_iter().map(|block| block.text).collect(),
+            Vec::new(),
+        );
+    }
+
+    // Leave room for the manifest, which names every file in the worst case.
+    let manifest_reserve: usize = tokenizer.count(MANIFEST_HEADER)
+        + blocks
+            .iter()
+            .map(|block| tokenizer.count(&block.display) + 16)
+            .sum::<usize>();
+    let mut allowances = vec![0usize; blocks.len()];
+    let mut remaining = available.saturating_sub(manifest_reserve);
+
+    let mut priorities: Vec<u8> = blocks.iter().map(|block| block.priority).collect();
+    priorities.sort_unstable();
+    priorities.dedup();
+    for priority in priorities {
+        let mut tier: Vec<usize> = (0..blocks.len())
+            .filter(|&index| blocks[index].priority == priority)
+            .collect();
+        tier.sort_by_key(|&index| costs[index]);
+
+        let count = tier.len();
+        for (position, &index) in tier.iter().enumerate() {
+            let share = remaining / (count - position);
+            let allowance = costs[index].min(share);
+            allowances[index] = allowance;
+            remaining -= allowance;
+        }
+    }
+
+    let mut text = String::new();
+    let mut cuts = Vec::new();
+    for ((block, allowance), cost) in blocks.into_iter().zip(allowances).zip(costs) {
+        if allowance >= cost {
+            text.push_str(&block.text);
+            continue;
+        }
+        if allowance < MIN_SHORTENED_TOKENS {
+            cuts.push(Cut {
+                display: block.display,
+                description: format!("dropped ({cost} tokens)"),
+            });
+            continue;
+        }
+
+        let (shortened, kept_lines, total_lines) = shorten(&block.text, allowance, tokenizer);
+        text.push_str(shortened);
+        text.push_str(&format!(
+            "\n...[truncated to fit the prompt budget: kept {kept_lines} of {total_lines} lines]...\n"
+        ));
+        cuts.push(Cut {
+            display: block.display,
+            description: format!("shortened to {kept_lines} of {total_lines} lines"),
+        });
+    }
+
+    (render_cuts(&cuts) + &text, cuts)
+}
+
+/// Splits blocks for the summarizing mode: the blocks that stay whole, in their original order,
+/// and the overflow to summarize, most important first.
+///
+/// Blocks are kept whole in priority order, smallest first within a priority, as long as they fit
+/// next to the room reserved for summarizing everything after them (at most `max_summaries`
+/// summaries of `summary_tokens` each).
+pub fn split_overflow(
+    blocks: Vec<PendingBlock>,
+    available: usize,
+    tokenizer: Tokenizer,
+    summary_tokens: usize,
+    max_summaries: usize,
+) -> (Vec<PendingBlock>, Vec<PendingBlock>) {
+    let costs: Vec<usize> = blocks
+        .iter()
+        .map(|block| tokenizer.count(&block.text))
+        .collect();
+    if costs.iter().sum::<usize>() <= available {
+        return (blocks, Vec::new());
+    }
+
+    let mut order: Vec<usize> = (0..blocks.len()).collect();
+    order.sort_by_key(|&index| (blocks[index].priority, costs[index]));
+
+    let mut kept = vec![false; blocks.len()];
+    let mut used = 0;
+    for (position, &index) in order.iter().enumerate() {
+        let later = (order.len() - position - 1).min(max_summaries);
+        if used + costs[index] + later * summary_tokens <= available {
+            kept[index] = true;
+            used += costs[index];
+        }
+    }
+
+    let overflow_order: Vec<usize> = order.into_iter().filter(|&index| !kept[index]).collect();
+    let mut slots: Vec<Option<PendingBlock>> = blocks.into_iter().map(Some).collect();
+    let overflow = overflow_order
+        .iter()
+        .filter_map(|&index| slots[index].take())
+        .collect();
+    let whole = slots.into_iter().flatten().collect();
+    (whole, overflow)
+}
+
+/// Splits `text` at line boundaries into pieces of at most about `max_tokens` each. A single line
+/// longer than that becomes a piece of its own.
+pub fn split_chunks(text: &str, max_tokens: usize, tokenizer: Tokenizer) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    let mut used = 0;
+    for line in text.split_inclusive('\n') {
+        let cost = tokenizer.count(line);
+        if used + cost > max_tokens && end > start {
+            chunks.push(&text[start..end]);
+            start = end;
+            used = 0;
+        }
+        used += cost;
+        end += line.len();
+    }
+    if end > start {
+        chunks.push(&text[start..end]);
+    }
+    chunks
+}
+
+/// The longest prefix of `text` within `max_tokens` that ends at a line boundary, with the number
+/// of content lines (after the `===== FILE` header) kept and in total. Room is left for the marker
+/// appended afterwards.
+fn shorten(text: &str, max_tokens: usize, tokenizer: Tokenizer) -> (&str, usize, usize) {
+    let header_end = text
+        .find(" =====\n")
+        .map_or(0, |index| index + " =====\n".len());
+    let content = &text[header_end..];
+    let total_lines = content.trim_end_matches('\n').lines().count();
+
+    let mut budget = max_tokens
+        .saturating_sub(tokenizer.count(&text[..header_end]))
+        .saturating_sub(MARKER_TOKENS);
+    let mut end = header_end;
+    let mut kept_lines = 0;
+    for line in content.split_inclusive('\n') {
+        let cost = tokenizer.count(line);
+        if cost > budget || kept_lines == total_lines {
+            break;
+        }
+        budget -= cost;
+        end += line.len();
+        kept_lines += 1;
+    }
+    (&text[..end], kept_lines, total_lines)
+}
+
+/// Renders the manifest listing files that were shortened or dropped.
+fn render_cuts(cuts: &[Cut]) -> String {
+    let mut text = String::from(MANIFEST_HEADER);
+    for cut in cuts {
+        text.push_str(&format!("- {}: {}\n", cut.display, cut.description));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "let value = compute(input, options)?;  \n";
+
+    fn block(display: &str, priority: u8, lines: usize, line: &str) -> PendingBlock {
+        PendingBlock {
+            display: display.to_string(),
+            priority,
+            text: format!("\n\n===== FILE: {display} =====\n{}", line.repeat(lines)),
+        }
+    }
+
+    fn cut<'a>(cuts: &'a [Cut], display: &str) -> &'a str {
+        cuts.iter()
+            .find(|cut| cut.display == display)
+            .map(|cut| cut.description.as_str())
+            .unwrap_or_else(|| panic!("{display} was not cut"))
+    }
+
+    #[test]
+    fn blocks_that_fit_are_returned_as_they_are() {
+        let blocks = vec![
+            block("a.rs", 0, 10, LINE),
+            block("b.rs", FileOrigin::AutoSelected.priority(), 10, LINE),
+        ];
+        let expected: String = blocks.iter().map(|block| block.text.as_str()).collect();
+        let (text, cuts) = fit_blocks(blocks, 10_000, Tokenizer::Approx);
+        assert_eq!(text, expected);
+        assert!(cuts.is_empty());
+    }
+
+    #[test]
+    fn higher_priorities_keep_their_space_first() {
+        let requested = block("requested.rs", FileOrigin::Requested.priority(), 400, LINE);
+        let auto = block("auto.rs", FileOrigin::AutoSelected.priority(), 400, LINE);
+        let (text, cuts) = fit_blocks(vec![auto, requested.clone()], 6_000, Tokenizer::Approx);
+
+        assert!(text.starts_with(MANIFEST_HEADER));
+        assert!(text.contains(&requested.text));
+        assert_eq!(cuts.len(), 1);
+        assert!(cut(&cuts, "auto.rs").starts_with("shortened to "));
+        assert!(cut(&cuts, "auto.rs").ends_with(" of 400 lines"));
+        // The original order is kept: the shortened block comes before the whole one.
+        assert!(text.find("auto.rs =====").unwrap() < text.find("requested.rs =====").unwrap());
+    }
+
+    #[test]
+    fn blocks_whose_share_is_too_small_are_dropped() {
+        let requested = block("requested.rs", FileOrigin::Requested.priority(), 400, LINE);
+        let changed = block("changed.rs", FileOrigin::Changed.priority(), 400, LINE);
+        let auto = block("auto.rs", FileOrigin::AutoSelected.priority(), 400, LINE);
+        let (text, cuts) = fit_blocks(
+            vec![requested.clone(), changed, auto],
+            4_500,
+            Tokenizer::Approx,
+        );
+
+        assert!(text.contains(&requested.text));
+        assert!(cut(&cuts, "changed.rs").starts_with("dropped ("));
+        assert!(cut(&cuts, "auto.rs").starts_with("dropped ("));
+        assert!(!text.contains("===== FILE: changed.rs"));
+        assert!(!text.contains("===== FILE: auto.rs"));
+        assert!(text.contains("- changed.rs: dropped ("));
+        assert!(text.contains("- auto.rs: dropped ("));
+    }
+
+    #[test]
+    fn space_is_shared_evenly_within_a_priority() {
+        let small = block("small.rs", 0, 10, LINE);
+        let (text, cuts) = fit_blocks(
+            vec![
+                block("big1.rs", 0, 400, LINE),
+                small.clone(),
+                block("big2.rs", 0, 400, LINE),
+            ],
+            5_000,
+            Tokenizer::Approx,
+        );
+
+        assert!(text.contains(&small.text));
+        assert_eq!(cuts.len(), 2);
+        assert_eq!(cut(&cuts, "big1.rs"), cut(&cuts, "big2.rs"));
+    }
+
+    #[test]
+    fn multibyte_text_is_cut_at_a_line_boundary() {
+        let line = "ünïcödé — 日本語のテキスト ✓\n";
+        let blocks = vec![
+            block("a.txt", 0, 10, LINE),
+            block("wide.txt", 1, 2_000, line),
+        ];
+        let (text, cuts) = fit_blocks(blocks, 5_000, Tokenizer::Approx);
+
+        let description = cut(&cuts, "wide.txt");
+        assert!(description.starts_with("shortened to "));
+        let kept = text
+            .split("===== FILE: wide.txt =====\n")
+            .nth(1)
+            .unwrap()
+            .split("\n...[truncated to fit the prompt budget")
+            .next()
+            .unwrap();
+        assert!(!kept.is_empty());
+        assert!(
+            kept.split_inclusive('\n')
+                .all(|kept_line| kept_line == line)
+        );
+        assert_eq!(
+            description,
+            format!("shortened to {} of 2000 lines", kept.lines().count())
+        );
+    }
+
+    #[test]
+    fn a_single_line_over_the_budget_keeps_no_lines() {
+        let long = format!("{}\n", "é".repeat(10_000));
+        let text = format!("\n\n===== FILE: min.js =====\n{long}");
+        let (shortened, kept, total) = shorten(&text, 600, Tokenizer::Approx);
+        assert_eq!(shortened, "\n\n===== FILE: min.js =====\n");
+        assert_eq!((kept, total), (0, 1));
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::traces::Frame;
+
+/// At most this many diagnostics are listed in the prompt.
+const MAX_DIAGNOSTICS: usize = 100;
+/// Unrecognized input is passed through raw, keeping at most this many trailing bytes.
+const MAX_RAW_BYTES: usize = 16 * 1024;
+
+/// `src/app.ts(12,5): error TS2322: Type 'string' is not assignable to type 'number'.`
+static TSC_PLAIN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.+?)\((\d+),(\d+)\): (error|warning|message) (TS\d+): (.*)$")
+        .expect("valid regex")
+});
+
+/// `src/app.ts:12:5 - error TS2322: Type 'string' is not assignable to type 'number'.`
+static TSC_PRETTY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.+?):(\d+):(\d+) - (error|warning|message) (TS\d+): (.*)$").expect("valid regex")
+});
+
+/// One compiler or linter finding.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Which format it came from: `rustc`, `tsc` or `eslint`.
+    pub tool: &'static str,
+    pub severity: String,
+    pub code: Option<String>,
+    pub message: String,
+    pub path: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// Span labels and `help:`/`note:` children, one per line.
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// The location the diagnostic points at, as a frame to excerpt.
+    pub fn frame(&self) -> Option<Frame> {
+        Some(Frame {
+            path: self.path.clone()?,
+            line: self.line.filter(|line| *line > 0)?,
+        })
+    }
+
+    fn summary(&self) -> String {
+        let code = self
+            .code
+            .as_ref()
+            .map(|code| format!("[{code}]"))
+            .unwrap_or_default();
+        let location = match (&self.path, self.line, self.column) {
+            (Some(path), Some(line), Some(column)) => format!(" {path}:{line}:{column}"),
+            (Some(path), Some(line), None) => format!(" {path}:{line}"),
+            (Some(path), None, _) => format!(" {path}"),
+            (None, ..) => String::new(),
+        };
+        format!("{}{code}{location}: {}", self.severity, self.message)
+    }
+}
+
+/// Parses machine-readable compiler or linter output: `cargo --message-format=json` (or rustc
+/// `--error-format=json`) lines, an ESLint `--format json` report, or `tsc` output. Duplicates,
+/// such as the same warning reported for a lib and its test target, are dropped.
+pub fn parse_diagnostics(text: &str) -> Vec<Diagnostic> {
+    let diagnostics = match serde_json::from_str::<Value>(text.trim()) {
+        Ok(Value::Array(files)) => parse_eslint(&files),
+        _ => text.lines().filter_map(parse_line).collect(),
+    };
+
+    let mut seen = HashSet::new();
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| seen.insert(diagnostic.summary()))
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<Diagnostic> {
+    let line = line.trim_end();
+    if line.starts_with('{') {
+        let value: Value = serde_json::from_str(line).ok()?;
+        let message = match value.get("reason").and_then(Value::as_str) {
+            Some("compiler-message") => value.get("message")?,
+            Some(_) => return None,
+            None => &value,
+        };
+        return parse_rustc(message);
+    }
+
+    let caps = TSC_PLAIN
+        .captures(line)
+        .or_else(|| TSC_PRETTY.captures(line))?;
+    Some(Diagnostic {
+        tool: "tsc",
+        severity: caps[4].to_string(),
+        code: Some(caps[5].to_string()),
+        message: caps[6].to_string(),
+        path: Some(caps[1].trim().replace('\\', "/")),
+        line: caps[2].parse().ok(),
+        column: caps[3].parse().ok(),
+        notes: Vec::new(),
+    })
+}
+
+/// A rustc JSON diagnostic. Messages without spans ("aborting due to 2 previous errors",
+/// "3 warnings emitted") are summaries and are skipped.
+fn parse_rustc(message: &Value) -> Option<Diagnostic> {
+    let spans = message.get("spans")?.as_array()?;
+    let primary = spans
+        .iter()
+        .find(|span| span.get("is_primary").and_then(Value::as_bool) == Some(true))
+        .or_else(|| spans.first())?;
+
+    let mut notes = Vec::new();
+    if let Some(label) = primary.get("label").and_then(Value::as_str) {
+        notes.push(label.to_string());
+    }
+    for child in message
+        .get("children")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let level = child.get("level").and_then(Value::as_str).unwrap_or("note");
+        if let Some(text) = child.get("message").and_then(Value::as_str) {
+            notes.push(format!("{level}: {text}"));
+        }
+    }
+
+    Some(Diagnostic {
+        tool: "rustc",
+        severity: message.get("level")?.as_str()?.to_string(),
+        code: message
+            .pointer("/code/code")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        message: message.get("message")?.as_str()?.to_string(),
+        path: primary
+            .get("file_name")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        line: primary
+            .get("line_start")
+            .and_then(Value::as_u64)
+            .map(|line| line as usize),
+        column: primary
+            .get("column_start")
+            .and_then(Value::as_u64)
+            .map(|column| column as usize),
+        notes,
+    })
+}
+
+fn parse_eslint(files: &[Value]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for file in files {
+        let Some(path) = file.get("filePath").and_then(Value::as_str) else {
+            continue;
+        };
+        for message in file
+            .get("messages")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let fatal = message.get("fatal").and_then(Value::as_bool) == Some(true);
+            let severity = match message.get("severity").and_then(Value::as_u64) {
+                _ if fatal => "error",
+                Some(2) => "error",
+                _ => "warning",
+            };
+            diagnostics.push(Diagnostic {
+                tool: "eslint",
+                severity: severity.to_string(),
+                code: message
+                    .get("ruleId")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                message: message
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                path: Some(path.replace('\\', "/")),
+                line: message
+                    .get("line")
+                    .and_then(Value::as_u64)
+                    .map(|line| line as usize),
+                column: message
+                    .get("column")
+                    .and_then(Value::as_u64)
+                    .map(|column| column as usize),
+                notes: Vec::new(),
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Renders the "Diagnostics" prompt section body: one line per diagnostic with its notes
+/// indented below. When nothing could be parsed, the tail of the raw input is shown instead.
+pub fn render_diagnostics(diagnostics: &[Diagnostic], raw: &str) -> String {
+    if diagnostics.is_empty() {
+        let raw = raw.trim();
+        let mut start = raw.len().saturating_sub(MAX_RAW_BYTES);
+        while !raw.is_char_boundary(start) {
+            start += 1;
+        }
+        let omitted = if start > 0 { "...\n" } else { "" };
+        return format!(
+            "Unrecognized diagnostics format; raw output follows.\n```\n{omitted}{}\n```\n",
+            &raw[start..]
+        );
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == "error")
+        .count();
+    let mut text = format!(
+        "{} diagnostics ({errors} errors) from {}:\n",
+        diagnostics.len(),
+        tools(diagnostics)
+    );
+    for diagnostic in diagnostics.iter().take(MAX_DIAGNOSTICS) {
+        text.push_str(&format!("- {}\n", diagnostic.summary()));
+        for note in &diagnostic.notes {
+            text.push_str(&format!("  {note}\n"));
+        }
+    }
+    if diagnostics.len() > MAX_DIAGNOSTICS {
+        text.push_str(&format!(
+            "- ...and {} more\n",
+            diagnostics.len() - MAX_DIAGNOSTICS
+        ));
+    }
+    text
+}
+
+fn tools(diagnostics: &[Diagnostic]) -> String {
+    let mut tools: Vec<&str> = Vec::new();
+    for diagnostic in diagnostics {
+        if !tools.contains(&diagnostic.tool) {
+            tools.push(diagnostic.tool);
+        }
+    }
+    tools.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARGO_JSON: &str = r#"{"reason":"compiler-artifact","package_id":"app 0.1.0","target":{"name":"app"},"fresh":true}
+{"reason":"compiler-message","package_id":"app 0.1.0","message":{"message":"mismatched types","code":{"code":"E0308","explanation":null},"level":"error","spans":[{"file_name":"src/lib.rs","line_start":12,"line_end":12,"column_start":9,"column_end":14,"is_primary":true,"label":"expected `u32`, found `&str`"}],"children":[{"message":"try using a conversion method","level":"help","spans":[],"children":[]}],"rendered":"error[E0308]: mismatched types"}}
+{"reason":"compiler-message","package_id":"app 0.1.0","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/main.rs","line_start":3,"line_end":3,"column_start":9,"column_end":10,"is_primary":true,"label":null}],"children":[],"rendered":"warning: unused variable"}}
+{"reason":"compiler-message","package_id":"app 0.1.0","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/main.rs","line_start":3,"line_end":3,"column_start":9,"column_end":10,"is_primary":true,"label":null}],"children":[],"rendered":"warning: unused variable"}}
+{"reason":"build-finished","success":false}
+"#;
+
+    const RUSTC_JSON: &str = r#"{"$message_type":"diagnostic","message":"cannot find value `y` in this scope","code":{"code":"E0425","explanation":null},"level":"error","spans":[{"file_name":"main.rs","line_start":2,"line_end":2,"column_start":13,"column_end":14,"is_primary":true,"label":"not found in this scope"}],"children":[],"rendered":"error[E0425]"}
+{"$message_type":"diagnostic","message":"aborting due to 1 previous error","code":null,"level":"error","spans":[],"children":[],"rendered":"error: aborting"}
+"#;
+
+    const ESLINT_JSON: &str = r#"[
+  {"filePath":"C:\\web\\src\\app.js","messages":[
+    {"ruleId":"no-undef","severity":2,"message":"'foo' is not defined.","line":4,"column":3},
+    {"ruleId":"semi","severity":1,"message":"Missing semicolon.","line":7,"column":20},
+    {"ruleId":null,"fatal":true,"severity":2,"message":"Parsing error: Unexpected token","line":9,"column":1}
+  ],"errorCount":2,"warningCount":1},
+  {"filePath":"/web/src/clean.js","messages":[],"errorCount":0,"warningCount":0}
+]"#;
+
+    const TSC: &str =
+        "src/app.ts(12,5): error TS2322: Type 'string' is not assignable to type 'number'.
+src\\util.ts:3:1 - warning TS6133: 'unused' is declared but its value is never read.
+
+Found 2 errors in 2 files.
+";
+
+    fn summaries(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(Diagnostic::summary).collect()
+    }
+
+    #[test]
+    fn parses_cargo_json_messages() {
+        let diagnostics = parse_diagnostics(CARGO_JSON);
+        assert_eq!(
+            summaries(&diagnostics),
+            [
+                "error[E0308] src/lib.rs:12:9: mismatched types",
+                "warning[unused_variables] src/main.rs:3:9: unused variable: `x`",
+            ]
+        );
+        assert!(
+            diagnostics
+                .iter()
+                .all(|diagnostic| diagnostic.tool == "rustc")
+        );
+        assert_eq!(
+            diagnostics[0].notes,
+            [
+                "expected `u32`, found `&str`",
+                "help: try using a conversion method"
+            ]
+        );
+        let frame = diagnostics[0].frame().unwrap();
+        assert_eq!((frame.path.as_str(), frame.line), ("src/lib.rs", 12));
+    }
+
+    #[test]
+    fn parses_rustc_json_and_skips_summaries() {
+        let diagnostics = parse_diagnostics(RUSTC_JSON);
+        assert_eq!(
+            summaries(&diagnostics),
+            ["error[E0425] main.rs:2:13: cannot find value `y` in this scope"]
+        );
+        assert_eq!(diagnostics[0].notes, ["not found in this scope"]);
+    }
+
+    #[test]
+    fn parses_eslint_reports() {
+        let diagnostics = parse_diagnostics(ESLINT_JSON);
+        assert_eq!(
+            summaries(&diagnostics),
+            [
+                "error[no-undef] C:/web/src/app.js:4:3: 'foo' is not defined.",
+                "warning[semi] C:/web/src/app.js:7:20: Missing semicolon.",
+                "error C:/web/src/app.js:9:1: Parsing error: Unexpected token",
+            ]
+        );
+        assert!(
+            diagnostics
+                .iter()
+                .all(|diagnostic| diagnostic.tool == "eslint")
+        );
+    }
+
+    #[test]
+    fn parses_plain_and_pretty_tsc_output() {
+        let diagnostics = parse_diagnostics(TSC);
+        assert_eq!(
+            summaries(&diagnostics),
+            [
+                "error[TS2322] src/app.ts:12:5: Type 'string' is not assignable to type 'number'.",
+                "warning[TS6133] src/util.ts:3:1: 'unused' is declared but its value is never read.",
+            ]
+        );
+        assert!(
+            diagnostics
+                .iter()
+                .all(|diagnostic| diagnostic.tool == "tsc")
+        );
+    }
+
+    #[test]
+    fn renders_parsed_diagnostics_by_tool() {
+        let mut diagnostics = parse_diagnostics(CARGO_JSON);
+        diagnostics.extend(parse_diagnostics(TSC));
+        let text = render_diagnostics(&diagnostics, "");
+        assert!(text.starts_with("4 diagnostics (2 errors) from rustc, tsc:\n"));
+        assert!(
+            text.contains("- error[E0308] src/lib.rs:12:9: mismatched types\n  expected `u32`")
+        );
+    }
+
+    #[test]
+    fn unrecognized_input_falls_back_to_the_raw_tail() {
+        let raw = "FAIL tests/app.test.js\n  expected 1, received 2\n";
+        assert!(parse_diagnostics(raw).is_empty());
+        assert_eq!(
+            render_diagnostics(&[], raw),
+            "Unrecognized diagnostics format; raw output follows.\n```\nFAIL tests/app.test.js\n  expected 1, received 2\n```\n"
+        );
+
+        let long = format!("{}\nlast line", "é".repeat(MAX_RAW_BYTES));
+        let text = render_diagnostics(&[], &long);
+        assert!(text.contains("```\n...\n"));
+        assert!(text.ends_with("last line\n```\n"));
+        assert!(text.len() < MAX_RAW_BYTES + 100);
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+/// Documents larger than this are left out; it is also the API's limit for one input file.
+pub const MAX_DOCUMENT_BYTES: u64 = 32 * 1024 * 1024;
+/// Upper bound on the number of documents a request can attach.
+pub const MAX_DOCUMENTS: usize = 8;
+/// Rough prompt cost of one page of an uploaded PDF (its text plus an image of the page),
+/// reserved from the token budget.
+pub const PDF_PAGE_TOKENS: usize = 1_024;
+
+/// Document formats that can't be read as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Pdf,
+    Docx,
+}
+
+impl DocumentKind {
+    /// The kind of document `path` names, by extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "pdf" => Some(DocumentKind::Pdf),
+            "docx" => Some(DocumentKind::Docx),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DocumentKind::Pdf => "PDF",
+            DocumentKind::Docx => "Word document",
+        }
+    }
+}
+
+/// Whether `path` names a Markdown file, which `documents` accepts and includes as text.
+pub fn is_markdown_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            matches!(extension.to_ascii_lowercase().as_str(), "md" | "markdown")
+        })
+}
+
+/// A PDF to upload and attach as an `input_file` part.
+#[derive(Debug, Clone)]
+pub struct DocumentInput {
+    pub display: String,
+    /// File name sent with the upload.
+    pub filename: String,
+    pub pages: usize,
+    pub bytes: Vec<u8>,
+}
+
+/// Number of pages in a PDF, or why it could not be parsed. Blocking.
+pub fn pdf_page_count(bytes: &[u8]) -> Result<usize, String> {
+    lopdf::Document::load_mem(bytes)
+        .map(|document| document.get_pages().len())
+        .map_err(|err| format!("not a readable PDF: {err}"))
+}
+
+/// Renders the list of uploaded documents for the prompt text, in the order they are attached.
+pub fn render_document_list(documents: &[DocumentInput]) -> String {
+    let mut text =
+        String::from("Attached as files after this text and any images, in this order:\n");
+    for (index, document) in documents.iter().enumerate() {
+        text.push_str(&format!(
+            "{}. {} (PDF, {} page{}, {} bytes)\n",
+            index + 1,
+            document.display,
+            document.pages,
+            if document.pages == 1 { "" } else { "s" },
+            document.bytes.len()
+        ));
+    }
+    text
+}
+
+/// Extracts the text of a document, with a marker before each PDF page. Blocking.
+pub fn extract_text(kind: DocumentKind, bytes: &[u8]) -> Result<String, String> {
+    match kind {
+        DocumentKind::Pdf => {
+            let pages = pdf_extract::extract_text_from_mem_by_pages(bytes)
+                .map_err(|err| format!("could not extract text from the PDF: {err}"))?;
+            let mut text = String::new();
+            for (index, page) in pages.iter().enumerate() {
+                text.push_str(&format!("--- page {} ---\n", index + 1));
+                text.push_str(page.trim());
+                text.push_str("\n\n");
+            }
+            Ok(text)
+        }
+        DocumentKind::Docx => docx_text(bytes),
+    }
+}
+
+/// Paragraph text of a `.docx`: `w:t` runs joined within each `w:p`, tabs and breaks kept.
+fn docx_text(bytes: &[u8]) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|err| format!("not a readable Word document: {err}"))?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|err| format!("not a readable Word document: {err}"))?
+        .read_to_string(&mut xml)
+        .map_err(|err| format!("not a readable Word document: {err}"))?;
+
+    let mut reader = Reader::from_str(&xml);
+    let mut text = String::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(tag)) if tag.name().as_ref() == b"w:t" => in_text = true,
+            Ok(Event::End(tag)) => match tag.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:p" => text.push('\n'),
+                _ => {}
+            },
+            Ok(Event::Empty(tag)) => match tag.name().as_ref() {
+                b"w:tab" => text.push('\t'),
+                b"w:br" | b"w:cr" => text.push('\n'),
+                _ => {}
+            },
+            Ok(Event::Text(run)) if in_text => {
+                let run = run
+                    .decode()
+                    .map_err(|err| format!("malformed Word document: {err}"))?;
+                text.push_str(
+                    &quick_xml::escape::unescape(&run)
+                        .map_err(|err| format!("malformed Word document: {err}"))?,
+                );
+            }
+            Ok(Event::GeneralRef(reference)) if in_text => {
+                // `&amp;` and friends arrive as separate events in quick-xml 0.38.
+                let name = reference
+                    .decode()
+                    .map_err(|err| format!("malformed Word document: {err}"))?;
+                let entity = format!("&{name};");
+                text.push_str(
+                    &quick_xml::escape::unescape(&entity).unwrap_or(entity.as_str().into()),
+                );
+            }
+            Ok(Event::Eof) => break,
+            Err(err) => return Err(format!("malformed Word document: {err}")),
+            _ => {}
+        }
+    }
+    Ok(text)
+}
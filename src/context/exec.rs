@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+
+/// How long to wait for output pipes to close after a timed-out command is killed. Processes it
+/// spawned may still hold them open.
+const PIPE_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How a captured command ended.
+#[derive(Debug, Clone)]
+pub enum CommandStatus {
+    Exited(Option<i32>),
+    TimedOut(Duration),
+    FailedToStart(String),
+}
+
+/// What a command wrote to one stream: the first and the last bytes of it, however much it
+/// wrote.
+#[derive(Debug, Clone, Default)]
+pub struct Captured {
+    head: Vec<u8>,
+    /// The bytes after `head`, of which only the most recent are kept.
+    tail: VecDeque<u8>,
+    /// How many bytes the stream carried in all.
+    pub total: u64,
+}
+
+impl Captured {
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Whether bytes between the head and the tail were dropped.
+    fn has_gap(&self) -> bool {
+        self.total > (self.head.len() + self.tail.len()) as u64
+    }
+
+    /// Everything kept, with a marker where bytes were dropped.
+    fn text(&self) -> String {
+        if !self.has_gap() {
+            return String::from_utf8_lossy(&self.end()).into_owned();
+        }
+        let tail: Vec<u8> = self.tail.iter().copied().collect();
+        format!(
+            "{}\n...\n{}",
+            String::from_utf8_lossy(&self.head),
+            String::from_utf8_lossy(&tail)
+        )
+    }
+
+    /// The kept bytes that end the stream, without a gap.
+    fn end(&self) -> Vec<u8> {
+        let mut bytes = if self.has_gap() {
+            Vec::new()
+        } else {
+            self.head.clone()
+        };
+        bytes.extend(self.tail.iter());
+        bytes
+    }
+}
+
+/// Exit status and output of one command run for context.
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub command: String,
+    pub status: CommandStatus,
+    pub duration: Duration,
+    pub stdout: Captured,
+    pub stderr: Captured,
+}
+
+impl CommandOutput {
+    /// Exit code for logging; `None` when the command was killed, timed out or never started.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.status {
+            CommandStatus::Exited(code) => code,
+            _ => None,
+        }
+    }
+
+    /// Stdout followed by stderr, for scanning for stack frames.
+    pub fn combined_text(&self) -> String {
+        format!("{}\n{}", self.stdout.text(), self.stderr.text())
+    }
+}
+
+/// Runs `argv` directly (not through a shell) in `dir` with stdin closed, killing it after
+/// `timeout`. Of each stream, only the first and last `max_bytes` are kept in memory.
+#[tracing::instrument(level = "debug", skip_all, fields(command = %command))]
+pub async fn run_command(
+    command: &str,
+    argv: &[String],
+    dir: &Path,
+    timeout: Duration,
+    max_bytes: usize,
+) -> CommandOutput {
+    let started = Instant::now();
+    let finish = |status, stdout, stderr| CommandOutput {
+        command: command.to_string(),
+        status,
+        duration: started.elapsed(),
+        stdout,
+        stderr,
+    };
+
+    let Some((program, args)) = argv.split_first() else {
+        return finish(
+            CommandStatus::FailedToStart("empty command".to_string()),
+            Captured::default(),
+            Captured::default(),
+        );
+    };
+
+    let mut child = match Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            return finish(
+                CommandStatus::FailedToStart(err.to_string()),
+                Captured::default(),
+                Captured::default(),
+            );
+        }
+    };
+
+    let stdout = child.stdout.take().map(|pipe| capture(pipe, max_bytes));
+    let stderr = child.stderr.take().map(|pipe| capture(pipe, max_bytes));
+
+    let status = match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(Ok(status)) => CommandStatus::Exited(status.code()),
+        Ok(Err(err)) => CommandStatus::FailedToStart(err.to_string()),
+        Err(_) => {
+            let _ = child.kill().await;
+            CommandStatus::TimedOut(timeout)
+        }
+    };
+
+    finish(status, collect(stdout).await, collect(stderr).await)
+}
+
+/// Reads `pipe` to the end, keeping its first `max_bytes` and a ring of its last `max_bytes`.
+fn capture(
+    mut pipe: impl AsyncRead + Unpin + Send + 'static,
+    max_bytes: usize,
+) -> JoinHandle<Captured> {
+    tokio::spawn(async move {
+        let mut captured = Captured::default();
+        let mut buffer = [0u8; 8192];
+        while let Ok(read) = pipe.read(&mut buffer).await {
+            if read == 0 {
+                break;
+            }
+            captured.total += read as u64;
+            let to_head = read.min(max_bytes - captured.head.len());
+            captured.head.extend_from_slice(&buffer[..to_head]);
+            captured.tail.extend(&buffer[to_head..read]);
+            let excess = captured.tail.len().saturating_sub(max_bytes);
+            captured.tail.drain(..excess);
+        }
+        captured
+    })
+}
+
+async fn collect(reader: Option<JoinHandle<Captured>>) -> Captured {
+    let Some(reader) = reader else {
+        return Captured::default();
+    };
+    match tokio::time::timeout(PIPE_DRAIN_TIMEOUT, reader).await {
+        Ok(Ok(captured)) => captured,
+        _ => Captured::default(),
+    }
+}
+
+/// Renders a `===== COMMAND =====` block with the exit status and the tail of each stream, cut to
+/// `max_bytes` so the end of the output (where failures are usually reported) survives.
+pub fn render_command_output(output: &CommandOutput, max_bytes: usize) -> String {
+    let status = match &output.status {
+        CommandStatus::Exited(Some(code)) => format!("exit code {code}"),
+        CommandStatus::Exited(None) => "terminated by a signal".to_string(),
+        CommandStatus::TimedOut(timeout) => format!("killed after {}s timeout", timeout.as_secs()),
+        CommandStatus::FailedToStart(err) => format!("failed to start: {err}"),
+    };
+    let mut text = format!(
+        "\n\n===== COMMAND: {} ({status}, {:.1}s) =====\n",
+        output.command,
+        output.duration.as_secs_f64()
+    );
+    for (name, captured) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        if captured.is_empty() {
+            continue;
+        }
+        let (tail, cut) = tail(&captured.end(), max_bytes);
+        if cut || captured.has_gap() {
+            text.push_str(&format!(
+                "--- {name} (last {} of {} bytes) ---\n...",
+                tail.len(),
+                captured.total
+            ));
+        } else {
+            text.push_str(&format!("--- {name} ---\n"));
+        }
+        text.push_str(&tail);
+        if !tail.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// The last `max_bytes` of `bytes` as text, starting on a character boundary.
+fn tail(bytes: &[u8], max_bytes: usize) -> (String, bool) {
+    let text = String::from_utf8_lossy(bytes);
+    if text.len() <= max_bytes {
+        return (text.into_owned(), false);
+    }
+    let mut start = text.len() - max_bytes;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    (text[start..].to_string(), true)
+}
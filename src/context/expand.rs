@@ -0,0 +1,504 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+
+use super::archive::{ArchiveKind, Member, list_members};
+use super::load::MAX_FILE_BYTES;
+use super::oracleignore::Ignored;
+use super::roots::Roots;
+use super::spec::{FileSpec, Selection};
+
+/// Upper bound on the number of files a request can pull into the prompt.
+pub const MAX_CONTEXT_FILES: usize = 256;
+/// Upper bound on the combined on-disk size of those files.
+pub const MAX_CONTEXT_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Why a file is part of the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOrigin {
+    /// Named in the request, directly or through a directory or glob.
+    Requested,
+    /// Excerpt around a stack frame found in the problem or extra context.
+    FailurePath,
+    /// Excerpt around a span reported by compiler or linter diagnostics.
+    Diagnostic,
+    /// Picked by `auto_context` from the problem text.
+    AutoSelected,
+    /// Post-change version of a file touched by the requested git changes.
+    Changed,
+}
+
+impl FileOrigin {
+    /// Label added to the file header so the model knows why it is seeing the file.
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            FileOrigin::Requested => None,
+            FileOrigin::FailurePath => Some("failure path"),
+            FileOrigin::Diagnostic => Some("diagnostic"),
+            FileOrigin::AutoSelected => Some("auto-selected"),
+            FileOrigin::Changed => Some("changed"),
+        }
+    }
+}
+
+/// A concrete file selected for the prompt.
+#[derive(Debug, Clone)]
+pub struct ContextFile {
+    /// Path as shown to the model, relative to the base directory when the entry was relative.
+    pub display: String,
+    /// Path used to read the file.
+    pub path: PathBuf,
+    pub selection: Selection,
+    pub origin: FileOrigin,
+    /// Git revision to read the file at instead of the working tree.
+    pub revision: Option<String>,
+    /// Member to read from the archive at `path`.
+    pub member: Option<String>,
+}
+
+impl ContextFile {
+    /// The display path, with `@REV` when the file is read from git history.
+    pub fn display_name(&self) -> String {
+        match &self.revision {
+            Some(revision) => format!("{}@{revision}", self.display),
+            None => self.display.clone(),
+        }
+    }
+
+    /// The path that tells the file's type: the member's path for archive members.
+    pub fn inner_path(&self) -> &Path {
+        self.member.as_deref().map_or(&self.path, Path::new)
+    }
+}
+
+/// A `files` entry (or part of one) that did not make it into the prompt.
+#[derive(Debug, Clone)]
+pub struct OmittedEntry {
+    pub entry: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Expansion {
+    /// Files in request order; matches of each directory or glob entry are sorted by path so
+    /// the same request always yields the same prompt.
+    pub files: Vec<ContextFile>,
+    pub omitted: Vec<OmittedEntry>,
+}
+
+/// Expands `files` entries into concrete files.
+///
+/// Each entry is a literal path, a directory (walked recursively), or a glob such as
+/// `src/**/*.rs`. Literal paths may carry a git revision (`path@v0.1.0`) and a selection
+/// (`path:120-200`, `path:88±20`, `path#Symbol`). `archive.zip!/member` names a member of an
+/// archive, or with a glob or trailing `/` several of them. A `NAME:` prefix resolves the entry
+/// against a named root instead of the working directory, and shows up in the file's display path.
+/// Files the sandbox refuses (outside the readable roots, or matching a deny pattern) and files
+/// and directories a `.oracleignore` excludes are left out with the reason.
+/// Entries starting with `!` are exclusion globs applied to every other entry;
+/// an exclusion without a `/` matches file names (`!*.lock`), otherwise the whole path.
+/// Directory walks and globs honor `.gitignore`, `.ignore` and hidden-file rules. Literal paths
+/// are kept even when they don't exist so the read error reaches the prompt.
+pub fn expand_entries(entries: &[String], roots: &Roots) -> Expansion {
+    let mut expansion = Expansion::default();
+
+    let mut exclusions = Vec::new();
+    for pattern in entries.iter().filter_map(|entry| entry.strip_prefix('!')) {
+        match compile_glob(pattern) {
+            Ok(matcher) => exclusions.push((matcher, !pattern.contains('/'))),
+            Err(reason) => expansion.omitted.push(OmittedEntry {
+                entry: format!("!{pattern}"),
+                reason,
+            }),
+        }
+    }
+    let is_excluded = |display: &str| {
+        let name = Path::new(display)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        exclusions.iter().any(|(matcher, name_only)| {
+            if *name_only {
+                matcher.is_match(&name)
+            } else {
+                matcher.is_match(display)
+            }
+        })
+    };
+
+    let mut seen = HashSet::new();
+    let mut total_bytes = 0u64;
+
+    for entry in entries.iter().filter(|entry| !entry.starts_with('!')) {
+        let (base, root, rest) = roots.resolve(entry);
+        let prefix = root.map_or_else(String::new, |root| format!("{root}:"));
+        let mut spec = FileSpec::parse(rest);
+        // A file whose name really contains `@` (`icon@2x.png`) wins over the revision syntax.
+        if let Some(revision) = &spec.revision {
+            let unsplit = format!("{}@{revision}", spec.path);
+            if base.join(&unsplit).exists() {
+                spec.path = unsplit;
+                spec.revision = None;
+            }
+        }
+        // Likewise `!/` only names an archive member when the part before it is an archive.
+        if let Some(member) = &spec.member
+            && ArchiveKind::from_path(Path::new(&spec.path)).is_none()
+        {
+            spec.path = format!("{}!/{member}", spec.path);
+            spec.member = None;
+        }
+
+        if let Some(member) = spec.member.clone() {
+            if spec.revision.is_some() {
+                expansion.omitted.push(OmittedEntry {
+                    entry: entry.clone(),
+                    reason: "git revisions don't apply to archive members".to_string(),
+                });
+                continue;
+            }
+            let archive = base.join(&spec.path);
+            if let Err(violation) = roots.check(&archive) {
+                expansion.omitted.push(OmittedEntry {
+                    entry: entry.clone(),
+                    reason: violation.to_string(),
+                });
+                continue;
+            }
+            if let Some(ignored) = roots.ignored(&archive) {
+                expansion.omitted.push(OmittedEntry {
+                    entry: entry.clone(),
+                    reason: ignored.to_string(),
+                });
+                continue;
+            }
+            let members = match archive_members(&archive, &member, &spec.selection) {
+                Ok(members) => members,
+                Err(reason) => {
+                    expansion.omitted.push(OmittedEntry {
+                        entry: entry.clone(),
+                        reason,
+                    });
+                    continue;
+                }
+            };
+
+            let mut over_limit = 0usize;
+            for member in members {
+                let display = format!("{prefix}{}!/{}", spec.path, member.name);
+                if is_excluded(&display) {
+                    continue;
+                }
+                if let Err(violation) = roots.sandbox().check_member(&member.name) {
+                    expansion.omitted.push(OmittedEntry {
+                        entry: display,
+                        reason: violation.to_string(),
+                    });
+                    continue;
+                }
+                if !seen.insert((
+                    archive.clone(),
+                    Some(member.name.clone()),
+                    spec.selection.clone(),
+                    None,
+                )) {
+                    continue;
+                }
+                let size = member.size.min(MAX_FILE_BYTES);
+                if expansion.files.len() >= MAX_CONTEXT_FILES
+                    || total_bytes + size > MAX_CONTEXT_BYTES
+                {
+                    over_limit += 1;
+                    continue;
+                }
+                total_bytes += size;
+                expansion.files.push(ContextFile {
+                    display,
+                    path: archive.clone(),
+                    selection: spec.selection.clone(),
+                    origin: FileOrigin::Requested,
+                    revision: None,
+                    member: Some(member.name),
+                });
+            }
+            if over_limit > 0 {
+                expansion.omitted.push(OmittedEntry {
+                    entry: entry.clone(),
+                    reason: format!(
+                        "{over_limit} member(s) skipped to stay under the {MAX_CONTEXT_FILES}-file and {MAX_CONTEXT_BYTES}-byte limits"
+                    ),
+                });
+            }
+            continue;
+        }
+
+        let literal =
+            !is_glob(&spec.path) && (spec.revision.is_some() || !base.join(&spec.path).is_dir());
+        let mut pruned = Vec::new();
+        let candidates = if literal {
+            vec![spec.path.clone()]
+        } else if spec.revision.is_some() {
+            expansion.omitted.push(OmittedEntry {
+                entry: entry.clone(),
+                reason: "git revisions only apply to single files".to_string(),
+            });
+            continue;
+        } else if spec.selection != Selection::Whole {
+            expansion.omitted.push(OmittedEntry {
+                entry: entry.clone(),
+                reason: "line ranges and symbols only apply to single files".to_string(),
+            });
+            continue;
+        } else {
+            match walk_entry(rest, base, roots) {
+                Ok(walked) => {
+                    pruned = walked.ignored;
+                    walked.matches
+                }
+                Err(reason) => {
+                    expansion.omitted.push(OmittedEntry {
+                        entry: entry.clone(),
+                        reason,
+                    });
+                    continue;
+                }
+            }
+        };
+
+        let mut matched = 0usize;
+        let mut over_file_limit = 0usize;
+        let mut over_byte_limit = 0usize;
+
+        for (relative, ignored) in pruned {
+            let display = format!("{prefix}{relative}");
+            if is_excluded(display.trim_end_matches('/')) {
+                continue;
+            }
+            matched += 1;
+            note_ignored(&mut expansion.omitted, display, &ignored);
+        }
+
+        for relative in candidates {
+            let display = format!("{prefix}{relative}");
+            if is_excluded(&display) {
+                if literal {
+                    expansion.omitted.push(OmittedEntry {
+                        entry: display,
+                        reason: "matches an exclusion pattern".to_string(),
+                    });
+                }
+                continue;
+            }
+            matched += 1;
+
+            let path = base.join(&relative);
+            if let Err(violation) = roots.check(&path) {
+                expansion.omitted.push(OmittedEntry {
+                    entry: display,
+                    reason: violation.to_string(),
+                });
+                continue;
+            }
+            // Walks have already skipped what `.oracleignore` excludes.
+            if literal && let Some(ignored) = roots.ignored(&path) {
+                note_ignored(&mut expansion.omitted, display, &ignored);
+                continue;
+            }
+            if !seen.insert((
+                path.clone(),
+                None,
+                spec.selection.clone(),
+                spec.revision.clone(),
+            )) {
+                continue;
+            }
+
+            if expansion.files.len() >= MAX_CONTEXT_FILES {
+                over_file_limit += 1;
+                continue;
+            }
+
+            // Huge files are only read in part, so they count for at most the per-file cap.
+            let size = std::fs::metadata(&path)
+                .map(|meta| meta.len().min(MAX_FILE_BYTES))
+                .unwrap_or(0);
+            if total_bytes + size > MAX_CONTEXT_BYTES {
+                over_byte_limit += 1;
+                continue;
+            }
+
+            total_bytes += size;
+            expansion.files.push(ContextFile {
+                display,
+                path,
+                selection: spec.selection.clone(),
+                origin: FileOrigin::Requested,
+                revision: spec.revision.clone(),
+                member: None,
+            });
+        }
+
+        if !literal && matched == 0 {
+            expansion.omitted.push(OmittedEntry {
+                entry: entry.clone(),
+                reason: "matched no files".to_string(),
+            });
+        }
+        if over_file_limit > 0 {
+            expansion.omitted.push(OmittedEntry {
+                entry: entry.clone(),
+                reason: format!(
+                    "{over_file_limit} file(s) skipped after reaching the {MAX_CONTEXT_FILES}-file limit"
+                ),
+            });
+        }
+        if over_byte_limit > 0 {
+            expansion.omitted.push(OmittedEntry {
+                entry: entry.clone(),
+                reason: format!(
+                    "{over_byte_limit} file(s) skipped to stay under the {MAX_CONTEXT_BYTES}-byte limit"
+                ),
+            });
+        }
+    }
+
+    expansion
+}
+
+/// Records that `.oracleignore` kept `entry` out, once even when several entries reach it.
+fn note_ignored(omitted: &mut Vec<OmittedEntry>, entry: String, ignored: &Ignored) {
+    if !omitted.iter().any(|omitted| omitted.entry == entry) {
+        omitted.push(OmittedEntry {
+            entry,
+            reason: ignored.to_string(),
+        });
+    }
+}
+
+/// The members of `archive` that `pattern` names: one member, a glob, or everything under a
+/// directory (`dir/`, or `` for the whole archive). Selections only apply to a single member.
+fn archive_members(
+    archive: &Path,
+    pattern: &str,
+    selection: &Selection,
+) -> Result<Vec<Member>, String> {
+    let members = list_members(archive)?;
+    if is_glob(pattern) || pattern.is_empty() || pattern.ends_with('/') {
+        if *selection != Selection::Whole {
+            return Err("line ranges and symbols only apply to single files".to_string());
+        }
+        let matched: Vec<Member> = if is_glob(pattern) {
+            let matcher = compile_glob(pattern)?;
+            members
+                .into_iter()
+                .filter(|member| matcher.is_match(&member.name))
+                .collect()
+        } else {
+            members
+                .into_iter()
+                .filter(|member| member.name.starts_with(pattern))
+                .collect()
+        };
+        if matched.is_empty() {
+            return Err("matched no archive members".to_string());
+        }
+        return Ok(matched);
+    }
+    members
+        .into_iter()
+        .find(|member| member.name == pattern)
+        .map(|member| vec![member])
+        .ok_or_else(|| format!("archive has no member `{pattern}`"))
+}
+
+fn is_glob(entry: &str) -> bool {
+    entry.contains(['*', '?', '[', '{'])
+}
+
+fn compile_glob(pattern: &str) -> Result<GlobMatcher, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|err| format!("invalid glob pattern: {err}"))
+}
+
+/// What a directory or glob entry matched, in display form and sorted.
+struct Walked {
+    matches: Vec<String>,
+    /// Files and directories the walk skipped because of `.oracleignore`. Files are only listed
+    /// when the entry would have matched them.
+    ignored: Vec<(String, Ignored)>,
+}
+
+/// Walks a directory or glob entry.
+fn walk_entry(entry: &str, base: &Path, roots: &Roots) -> Result<Walked, String> {
+    let entry = entry.strip_prefix("./").unwrap_or(entry);
+    let prefix = glob_prefix(entry);
+    let matcher = if is_glob(entry) {
+        Some(compile_glob(entry)?)
+    } else {
+        None
+    };
+
+    let root = base.join(&prefix);
+    if !root.is_dir() {
+        return Err("matched no files".to_string());
+    }
+
+    if let Some(ignored) = roots.ignored(&root) {
+        let display = format!("{}/", prefix.to_string_lossy());
+        return Ok(Walked {
+            matches: Vec::new(),
+            ignored: vec![(display, ignored)],
+        });
+    }
+
+    let mut walker = WalkBuilder::new(&root);
+    walker.require_git(false);
+    let pruned = roots.prune_ignored(&mut walker, &root);
+    let mut matches: Vec<String> = walker
+        .build()
+        .filter_map(Result::ok)
+        .filter(|dent| dent.file_type().is_some_and(|kind| kind.is_file()))
+        .filter_map(|dent| {
+            let relative = dent.path().strip_prefix(&root).ok()?;
+            let display = prefix.join(relative).to_string_lossy().into_owned();
+            match &matcher {
+                Some(matcher) if !matcher.is_match(&display) => None,
+                _ => Some(display),
+            }
+        })
+        .collect();
+
+    matches.sort();
+
+    let mut ignored: Vec<(String, Ignored)> =
+        std::mem::take(&mut *pruned.lock().unwrap_or_else(|err| err.into_inner()))
+            .into_iter()
+            // `join` keeps the trailing `/` that marks a directory.
+            .map(|(relative, ignored)| {
+                (
+                    prefix.join(relative).to_string_lossy().into_owned(),
+                    ignored,
+                )
+            })
+            .filter(|(display, _)| match &matcher {
+                Some(matcher) if !display.ends_with('/') => matcher.is_match(display),
+                _ => true,
+            })
+            .collect();
+    ignored.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(Walked { matches, ignored })
+}
+
+/// Leading path components of an entry that contain no wildcards, i.e. where the walk starts.
+fn glob_prefix(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .take_while(|component| !is_glob(&component.as_os_str().to_string_lossy()))
+        .collect()
+}
@@ -0,0 +1,249 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use tokio::process::Command;
+
+/// Diffs longer than this are cut, keeping whole files where possible.
+const MAX_DIFF_BYTES: usize = 256 * 1024;
+/// Commits listed in the log that accompanies a diff.
+const MAX_LOG_COMMITS: usize = 30;
+/// Post-change files added at most when `include_changed_files` is set.
+pub const MAX_CHANGED_FILES: usize = 20;
+
+/// Which changes to show.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSource {
+    /// Uncommitted changes, staged or not, against `HEAD`.
+    WorkingTree,
+    /// Changes staged in the index, against `HEAD`.
+    Staged,
+    /// `BASE..TIP` or `BASE...TIP`; a single revision means `REV..HEAD`.
+    Range(String),
+}
+
+impl DiffSource {
+    pub fn title(&self) -> String {
+        match self {
+            DiffSource::WorkingTree => "Uncommitted changes (working tree vs HEAD)".to_string(),
+            DiffSource::Staged => "Staged changes (index vs HEAD)".to_string(),
+            DiffSource::Range(range) => format!("Commit range {}", normalize_range(range)),
+        }
+    }
+
+    /// Arguments selecting the changes for `git diff`.
+    fn diff_args(&self) -> Vec<String> {
+        match self {
+            DiffSource::WorkingTree => vec!["HEAD".to_string()],
+            DiffSource::Staged => vec!["--cached".to_string()],
+            DiffSource::Range(range) => vec![normalize_range(range)],
+        }
+    }
+
+    /// Revision holding the post-change version of files; `None` for the working tree.
+    pub fn tip(&self) -> Option<String> {
+        match self {
+            DiffSource::WorkingTree | DiffSource::Staged => None,
+            DiffSource::Range(range) => {
+                let range = normalize_range(range);
+                let tip = range
+                    .split_once("...")
+                    .or_else(|| range.split_once(".."))
+                    .map_or("", |(_, tip)| tip);
+                Some(if tip.is_empty() { "HEAD" } else { tip }.to_string())
+            }
+        }
+    }
+}
+
+fn normalize_range(range: &str) -> String {
+    let range = range.trim();
+    if range.contains("..") {
+        range.to_string()
+    } else {
+        format!("{range}..HEAD")
+    }
+}
+
+/// A diff plus the commit log that explains it.
+#[derive(Debug, Clone)]
+pub struct GitChanges {
+    pub title: String,
+    pub log: String,
+    pub diff: String,
+    pub diff_truncated: bool,
+    /// Files changed and still present after the change, relative to the directory git ran in.
+    pub changed_files: Vec<String>,
+    pub untracked_files: Vec<String>,
+}
+
+/// Collects the diff, a short commit log and the changed file list for `source` by running the
+/// local `git` binary in `dir`. Files matching the `exclude` pathspecs are left out of the diff
+/// and the file lists.
+#[tracing::instrument(level = "debug", fields(source = ?source))]
+pub async fn collect_changes(
+    source: &DiffSource,
+    dir: &Path,
+    exclude: &[String],
+) -> Result<GitChanges, String> {
+    if let DiffSource::Range(range) = source
+        && (range.trim().is_empty() || range.trim().starts_with('-'))
+    {
+        return Err(format!("Invalid git range `{range}`"));
+    }
+
+    let selector = source.diff_args();
+    let selector: Vec<&str> = selector.iter().map(String::as_str).collect();
+    let exclude: Vec<&str> = exclude.iter().map(String::as_str).collect();
+
+    let mut diff_args = vec!["diff", "--no-color", "--no-ext-diff", "-M"];
+    diff_args.extend(&selector);
+    diff_args.push("--");
+    diff_args.extend(&exclude);
+    let diff = git(dir, &diff_args).await?;
+
+    let mut names_args = vec!["diff", "--name-only", "--relative", "--diff-filter=d", "-z"];
+    names_args.extend(&selector);
+    names_args.push("--");
+    names_args.extend(&exclude);
+    let changed_files = git(dir, &names_args)
+        .await?
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let log_limit = format!("-{MAX_LOG_COMMITS}");
+    let log_format = "--format=%h %ad %an: %s";
+    let log = match source {
+        DiffSource::Range(_) => {
+            git(
+                dir,
+                &[
+                    "log",
+                    &log_limit,
+                    "--date=short",
+                    log_format,
+                    selector[0],
+                    "--",
+                ],
+            )
+            .await?
+        }
+        _ => {
+            git(
+                dir,
+                &["log", "-5", "--date=short", log_format, "HEAD", "--"],
+            )
+            .await?
+        }
+    };
+
+    let untracked_files = match source {
+        DiffSource::WorkingTree => {
+            let mut untracked_args = vec!["ls-files", "--others", "--exclude-standard", "-z", "--"];
+            untracked_args.extend(&exclude);
+            git(dir, &untracked_args).await?
+        }
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect(),
+        _ => Vec::new(),
+    };
+
+    let (diff, diff_truncated) = truncate_diff(diff);
+    Ok(GitChanges {
+        title: source.title(),
+        log,
+        diff,
+        diff_truncated,
+        changed_files,
+        untracked_files,
+    })
+}
+
+/// Raw contents of the file at `path` as of `revision`, in the repository containing it.
+#[tracing::instrument(level = "debug")]
+pub async fn show_file(revision: &str, path: &Path) -> Result<Vec<u8>, String> {
+    if revision.starts_with('-') {
+        return Err(format!("Invalid git revision `{revision}`"));
+    }
+    // Run from the file's directory so files under any root resolve in their own repository.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", path.display()))?
+        .to_string_lossy();
+    git_bytes(dir, &["show", &format!("{revision}:./{name}")]).await
+}
+
+/// Cuts the diff at a file boundary (`diff --git`) before [`MAX_DIFF_BYTES`] when possible.
+fn truncate_diff(diff: String) -> (String, bool) {
+    if diff.len() <= MAX_DIFF_BYTES {
+        return (diff, false);
+    }
+    let mut end = MAX_DIFF_BYTES;
+    while !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = diff[..end]
+        .rfind("\ndiff --git ")
+        .map_or(end, |boundary| boundary + 1);
+    (diff[..end].to_string(), true)
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let stdout = git_bytes(dir, args).await?;
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+async fn git_bytes(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "core.quotePath=false"])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|err| format!("Failed to run git: {err}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(output.stdout)
+}
+
+/// Renders a `#### title` subsection with the commit log and the diff.
+pub fn render_changes(changes: &GitChanges) -> String {
+    let mut text = format!("#### {}\n", changes.title);
+    if !changes.log.trim().is_empty() {
+        text.push_str("Commits:\n```\n");
+        text.push_str(changes.log.trim_end());
+        text.push_str("\n```\n");
+    }
+    if !changes.untracked_files.is_empty() {
+        text.push_str(&format!(
+            "Untracked files (not in the diff): {}\n",
+            changes.untracked_files.join(", ")
+        ));
+    }
+    if changes.diff.trim().is_empty() {
+        text.push_str("(no changes)\n\n");
+        return text;
+    }
+    text.push_str("```diff\n");
+    text.push_str(&changes.diff);
+    if !changes.diff.ends_with('\n') {
+        text.push('\n');
+    }
+    if changes.diff_truncated {
+        text.push_str("...[diff truncated]...\n");
+    }
+    text.push_str("```\n\n");
+    text
+}
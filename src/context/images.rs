@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+/// Images larger than this are left out; the API rejects anything much bigger.
+pub const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+/// Upper bound on the number of images a request can attach.
+pub const MAX_IMAGES: usize = 16;
+/// Rough prompt cost of one image at `auto` detail, reserved from the token budget.
+pub const IMAGE_TOKENS: usize = 1_536;
+
+/// Whether `path` names an image that can be attached: PNG, JPEG, WebP or GIF.
+pub fn is_image_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            matches!(
+                extension.to_ascii_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "webp" | "gif"
+            )
+        })
+}
+
+/// An image ready to be sent as an `input_image` part.
+#[derive(Debug, Clone)]
+pub struct ImageInput {
+    pub display: String,
+    pub media_type: &'static str,
+    pub bytes: usize,
+    /// `data:` URL holding the base64-encoded image.
+    pub data_url: String,
+}
+
+/// Checks that `bytes` really are a supported image and encodes them for the API. The type is
+/// taken from the file's signature, not its extension.
+pub fn encode_image(display: &str, bytes: &[u8]) -> Result<ImageInput, String> {
+    let media_type =
+        sniff_media_type(bytes).ok_or_else(|| "not a PNG, JPEG, WebP or GIF image".to_string())?;
+    Ok(ImageInput {
+        display: display.to_string(),
+        media_type,
+        bytes: bytes.len(),
+        data_url: format!("data:{media_type};base64,{}", STANDARD.encode(bytes)),
+    })
+}
+
+fn sniff_media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Renders the list of attached images for the prompt text, in the order they are attached.
+pub fn render_image_list(images: &[ImageInput]) -> String {
+    let mut text = String::from("Attached after this text, in this order:\n");
+    for (index, image) in images.iter().enumerate() {
+        text.push_str(&format!(
+            "{}. {} ({}, {} bytes)\n",
+            index + 1,
+            image.display,
+            image.media_type,
+            image.bytes
+        ));
+    }
+    text
+}
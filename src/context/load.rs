@@ -0,0 +1,423 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::spec::Selection;
+
+/// Files larger than this are never read whole: whole-file entries show a head and a tail
+/// window, line selections are streamed.
+pub const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Size of the head window shown for files over [`MAX_FILE_BYTES`].
+const HEAD_BYTES: u64 = 768 * 1024;
+/// Size of the tail window shown for files over [`MAX_FILE_BYTES`].
+const TAIL_BYTES: u64 = 256 * 1024;
+/// Bytes inspected to tell binary from text and guess the encoding.
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// How a file's bytes were turned into text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Encoding {
+    /// Note for the file header when the file was not plain UTF-8.
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Encoding::Utf8 => None,
+            Encoding::Utf16Le => Some("decoded from UTF-16LE"),
+            Encoding::Utf16Be => Some("decoded from UTF-16BE"),
+            Encoding::Latin1 => Some("not valid UTF-8, decoded as Latin-1"),
+        }
+    }
+}
+
+/// Text loaded for the prompt.
+#[derive(Debug, Clone)]
+pub struct LoadedText {
+    pub text: String,
+    pub encoding: Encoding,
+    /// Set when only the lines of a line selection were read: the first and last line `text`
+    /// holds (`None` when the file is shorter than the selection) and the file's line count.
+    pub streamed_lines: Option<(Option<(usize, usize)>, usize)>,
+    /// Size on disk when the middle of the file was left out.
+    pub truncated_from: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Loaded {
+    Text(LoadedText),
+    Binary { bytes: u64 },
+}
+
+/// Reads `path` for `selection` without ever holding more than about [`MAX_FILE_BYTES`] of it in
+/// memory. Blocking; run it on a blocking thread.
+pub fn load_file(path: &Path, selection: &Selection) -> io::Result<Loaded> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut sniff = Vec::with_capacity(SNIFF_BYTES);
+    (&mut file)
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut sniff)?;
+    let Some(encoding) = sniff_encoding(&sniff) else {
+        return Ok(Loaded::Binary { bytes: size });
+    };
+    file.seek(SeekFrom::Start(0))?;
+
+    if size <= MAX_FILE_BYTES {
+        let mut bytes = Vec::with_capacity(size as usize);
+        file.read_to_end(&mut bytes)?;
+        let (text, encoding) = decode(&bytes, encoding);
+        return Ok(Loaded::Text(LoadedText {
+            text,
+            encoding,
+            streamed_lines: None,
+            truncated_from: None,
+        }));
+    }
+
+    if let Some((start, end)) = selection.line_bounds()
+        && matches!(encoding, Encoding::Utf8 | Encoding::Latin1)
+    {
+        return stream_lines(BufReader::new(file), start, end, encoding).map(Loaded::Text);
+    }
+
+    let mut head = Vec::with_capacity(HEAD_BYTES as usize);
+    (&mut file).take(HEAD_BYTES).read_to_end(&mut head)?;
+    file.seek(SeekFrom::Start(size - TAIL_BYTES))?;
+    let mut tail = Vec::with_capacity(TAIL_BYTES as usize);
+    file.read_to_end(&mut tail)?;
+    Ok(Loaded::Text(head_and_tail(&head, &tail, size, encoding)))
+}
+
+/// Decodes bytes already in memory (such as a git blob) for `selection` the same way
+/// [`load_file`] decodes files.
+pub fn load_bytes(bytes: &[u8], selection: &Selection) -> Loaded {
+    let size = bytes.len() as u64;
+    let Some(encoding) = sniff_encoding(&bytes[..bytes.len().min(SNIFF_BYTES)]) else {
+        return Loaded::Binary { bytes: size };
+    };
+    if size <= MAX_FILE_BYTES {
+        let (text, encoding) = decode(bytes, encoding);
+        return Loaded::Text(LoadedText {
+            text,
+            encoding,
+            streamed_lines: None,
+            truncated_from: None,
+        });
+    }
+    if let Some((start, end)) = selection.line_bounds()
+        && matches!(encoding, Encoding::Utf8 | Encoding::Latin1)
+        && let Ok(loaded) = stream_lines(bytes, start, end, encoding)
+    {
+        return Loaded::Text(loaded);
+    }
+    let head = &bytes[..HEAD_BYTES as usize];
+    let tail = &bytes[bytes.len() - TAIL_BYTES as usize..];
+    Loaded::Text(head_and_tail(head, tail, size, encoding))
+}
+
+/// Joins the head and tail windows of a huge file at line boundaries around a marker.
+fn head_and_tail(head: &[u8], tail: &[u8], size: u64, encoding: Encoding) -> LoadedText {
+    let line_aligned = matches!(encoding, Encoding::Utf8 | Encoding::Latin1);
+    let head = match head.iter().rposition(|byte| *byte == b'\n') {
+        Some(newline) if line_aligned => &head[..=newline],
+        _ => head,
+    };
+    let tail = match tail.iter().position(|byte| *byte == b'\n') {
+        Some(newline) if line_aligned => &tail[newline + 1..],
+        _ => tail,
+    };
+    // A window without a newline (minified code, one-line JSON) can end or start inside a
+    // character; trim to whole characters so it still decodes as UTF-8.
+    let (head, tail) = match encoding {
+        Encoding::Utf8 => (
+            match std::str::from_utf8(head) {
+                Err(err) if err.error_len().is_none() => &head[..err.valid_up_to()],
+                _ => head,
+            },
+            &tail[tail
+                .iter()
+                .take(3)
+                .take_while(|byte| (0x80..0xC0).contains(*byte))
+                .count()..],
+        ),
+        _ => (head, tail),
+    };
+    let omitted = size - head.len() as u64 - tail.len() as u64;
+
+    let (head_text, encoding) = decode(head, encoding);
+    let (tail_text, _) = decode(tail, encoding);
+    LoadedText {
+        text: format!(
+            "{head_text}\n...[{omitted} bytes omitted: the file is {size} bytes, showing the first {} and last {} bytes]...\n{tail_text}",
+            head.len(),
+            tail.len()
+        ),
+        encoding,
+        streamed_lines: None,
+        truncated_from: Some(size),
+    }
+}
+
+/// Reads lines `start..=end` (1-based) of a byte-oriented file and counts the rest.
+fn stream_lines(
+    mut reader: impl BufRead,
+    start: usize,
+    end: usize,
+    encoding: Encoding,
+) -> io::Result<LoadedText> {
+    let mut selected = Vec::new();
+    let mut line = Vec::new();
+    let mut total = 0usize;
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        total += 1;
+        if (start..=end).contains(&total) && (selected.len() as u64) < MAX_FILE_BYTES {
+            selected.extend_from_slice(&line);
+        }
+    }
+
+    let lines = (start <= total).then(|| (start, end.min(total)));
+    let (text, encoding) = decode(&selected, encoding);
+    Ok(LoadedText {
+        text,
+        encoding,
+        streamed_lines: Some((lines, total)),
+        truncated_from: None,
+    })
+}
+
+/// Guesses the encoding from the first bytes; `None` means the file is binary.
+fn sniff_encoding(sniff: &[u8]) -> Option<Encoding> {
+    if sniff.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return Some(Encoding::Utf8);
+    }
+    if sniff.starts_with(&[0xFF, 0xFE]) {
+        return Some(Encoding::Utf16Le);
+    }
+    if sniff.starts_with(&[0xFE, 0xFF]) {
+        return Some(Encoding::Utf16Be);
+    }
+
+    // BOM-less UTF-16 text has a zero in nearly every high byte and almost none in the low ones.
+    if sniff.len() >= 4 {
+        let zeros_at = |parity: usize| {
+            sniff
+                .iter()
+                .skip(parity)
+                .step_by(2)
+                .filter(|byte| **byte == 0)
+                .count()
+        };
+        let pairs = sniff.len() / 2;
+        let (even, odd) = (zeros_at(0), zeros_at(1));
+        if odd * 10 >= pairs * 9 && even * 10 <= pairs {
+            return Some(Encoding::Utf16Le);
+        }
+        if even * 10 >= pairs * 9 && odd * 10 <= pairs {
+            return Some(Encoding::Utf16Be);
+        }
+    }
+
+    if sniff.contains(&0) {
+        return None;
+    }
+    let control = sniff
+        .iter()
+        .filter(|byte| **byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B))
+        .count();
+    if control * 10 > sniff.len() {
+        return None;
+    }
+
+    match std::str::from_utf8(sniff) {
+        // A multi-byte character cut off at the end of the sniffed window is still UTF-8.
+        Err(err) if err.error_len().is_some() => Some(Encoding::Latin1),
+        _ => Some(Encoding::Utf8),
+    }
+}
+
+/// Decodes `bytes`, falling back to Latin-1 when supposed UTF-8 turns out to be invalid.
+fn decode(bytes: &[u8], encoding: Encoding) -> (String, Encoding) {
+    match encoding {
+        Encoding::Utf8 => {
+            let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+            match std::str::from_utf8(bytes) {
+                Ok(text) => (text.to_string(), Encoding::Utf8),
+                Err(_) => decode(bytes, Encoding::Latin1),
+            }
+        }
+        Encoding::Latin1 => (
+            bytes.iter().map(|byte| char::from(*byte)).collect(),
+            Encoding::Latin1,
+        ),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let units = bytes.chunks_exact(2).map(|pair| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                _ => u16::from_be_bytes([pair[0], pair[1]]),
+            });
+            let text: String = char::decode_utf16(units)
+                .map(|unit| unit.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect();
+            (
+                text.strip_prefix('\u{FEFF}').unwrap_or(&text).to_string(),
+                encoding,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sniffing_tells_binary_from_text() {
+        assert_eq!(sniff_encoding(b"fn main() {}\n"), Some(Encoding::Utf8));
+        assert_eq!(sniff_encoding(b""), Some(Encoding::Utf8));
+        assert_eq!(
+            sniff_encoding(b"\x1b[31mred\x1b[0m\tok\r\n"),
+            Some(Encoding::Utf8)
+        );
+        assert_eq!(sniff_encoding(b"\x7fELF\x02\x01\x01\x00\x00\x00"), None);
+        assert_eq!(sniff_encoding(b"\x01\x02\x03\x04abc\x05\x06"), None);
+    }
+
+    #[test]
+    fn byte_order_marks_pick_the_encoding() {
+        assert_eq!(sniff_encoding(b"\xEF\xBB\xBFhi"), Some(Encoding::Utf8));
+        assert_eq!(
+            sniff_encoding(b"\xFF\xFEh\x00i\x00"),
+            Some(Encoding::Utf16Le)
+        );
+        assert_eq!(
+            sniff_encoding(b"\xFE\xFF\x00h\x00i"),
+            Some(Encoding::Utf16Be)
+        );
+    }
+
+    #[test]
+    fn utf16_without_a_bom_is_detected_from_its_zero_bytes() {
+        let text = "let answer = 42;\n";
+        assert_eq!(sniff_encoding(&utf16(text, true)), Some(Encoding::Utf16Le));
+        assert_eq!(sniff_encoding(&utf16(text, false)), Some(Encoding::Utf16Be));
+        assert_eq!(
+            decode(&utf16(text, false), Encoding::Utf16Be),
+            (text.to_string(), Encoding::Utf16Be)
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_falls_back_to_latin1() {
+        assert_eq!(sniff_encoding(b"caf\xe9 au lait"), Some(Encoding::Latin1));
+        assert_eq!(
+            decode(b"caf\xe9", Encoding::Latin1),
+            ("café".to_string(), Encoding::Latin1)
+        );
+        // Sniffed as UTF-8, but invalid further on.
+        assert_eq!(
+            decode(b"ok\nna\xefve", Encoding::Utf8),
+            ("ok\nna\u{ef}ve".to_string(), Encoding::Latin1)
+        );
+        // A character cut off by the end of the sniffed window is still UTF-8.
+        assert_eq!(
+            sniff_encoding("naïve".as_bytes().split_at(3).0),
+            Some(Encoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn decoding_drops_byte_order_marks() {
+        assert_eq!(
+            decode(b"\xEF\xBB\xBFhi", Encoding::Utf8),
+            ("hi".to_string(), Encoding::Utf8)
+        );
+        assert_eq!(
+            decode(b"\xFF\xFEh\x00i\x00", Encoding::Utf16Le),
+            ("hi".to_string(), Encoding::Utf16Le)
+        );
+        // An unpaired surrogate becomes a replacement character.
+        assert_eq!(
+            decode(&[0x00, 0xD8, b'a', 0x00], Encoding::Utf16Le),
+            ("\u{FFFD}a".to_string(), Encoding::Utf16Le)
+        );
+    }
+
+    #[test]
+    fn head_and_tail_cut_at_line_boundaries() {
+        let head = b"line 1\nline 2\nline";
+        let tail = b"ne 9\nline 10\n";
+        let loaded = head_and_tail(head, tail, 1000, Encoding::Utf8);
+
+        assert_eq!(
+            loaded.text,
+            "line 1\nline 2\n\n...[978 bytes omitted: the file is 1000 bytes, showing the first 14 and last 8 bytes]...\nline 10\n"
+        );
+        assert_eq!(loaded.truncated_from, Some(1000));
+        assert_eq!(loaded.encoding, Encoding::Utf8);
+    }
+
+    #[test]
+    fn head_and_tail_keep_whole_characters_without_newlines() {
+        let text = "é".repeat(10);
+        let bytes = text.as_bytes();
+        // Both windows start or end in the middle of a two-byte character.
+        let loaded = head_and_tail(&bytes[..5], &bytes[15..], 100, Encoding::Utf8);
+
+        assert_eq!(
+            loaded.text,
+            "éé\n...[92 bytes omitted: the file is 100 bytes, showing the first 4 and last 4 bytes]...\néé"
+        );
+        assert_eq!(loaded.encoding, Encoding::Utf8);
+    }
+
+    #[test]
+    fn large_buffers_honor_line_selections() {
+        let line = "0123456789012345678901234567890123456789012345678901234567890123\n";
+        let bytes = line.repeat(MAX_FILE_BYTES as usize / line.len() + 100);
+        let total = bytes.len() / line.len();
+
+        let Loaded::Text(loaded) =
+            load_bytes(bytes.as_bytes(), &Selection::Lines { start: 3, end: 4 })
+        else {
+            panic!("expected text");
+        };
+        assert_eq!(loaded.text, line.repeat(2));
+        assert_eq!(loaded.streamed_lines, Some((Some((3, 4)), total)));
+        assert_eq!(loaded.truncated_from, None);
+
+        let Loaded::Text(whole) = load_bytes(bytes.as_bytes(), &Selection::Whole) else {
+            panic!("expected text");
+        };
+        assert_eq!(whole.truncated_from, Some(bytes.len() as u64));
+    }
+
+    #[test]
+    fn binary_buffers_are_not_decoded() {
+        assert!(matches!(
+            load_bytes(b"\x00\x01\x02binary", &Selection::Whole),
+            Loaded::Binary { bytes: 9 }
+        ));
+    }
+}
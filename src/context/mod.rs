@@ -0,0 +1,127 @@
+//! Gathering project context for the prompt: the request's `files` entries, excerpts around
+//! pasted stack frames and diagnostics, captured command output, git diffs, files picked by
+//! `auto_context`, and the optional repository map.
+
+pub mod archive;
+pub mod auto;
+pub mod budget;
+pub mod diagnostics;
+pub mod documents;
+pub mod exec;
+pub mod expand;
+pub mod git;
+pub mod images;
+pub mod load;
+pub mod notebook;
+pub mod oracleignore;
+pub mod repo_map;
+pub mod roots;
+pub mod sandbox;
+pub mod spec;
+pub mod symbols;
+pub mod traces;
+
+use expand::ContextFile;
+use load::LoadedText;
+use spec::{Excerpt, Selection};
+
+/// The largest index at most `index` that falls on a char boundary of `text`.
+pub fn floor_char_boundary(text: &str, index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    let mut end = index;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    end
+}
+
+/// A rendered `===== FILE =====` block plus, when the selection could not be honored, why.
+pub struct FileBlock {
+    pub text: String,
+    pub problem: Option<String>,
+}
+
+/// Renders the part of `loaded` selected by `file` as one or more prompt blocks. Excerpts state
+/// the lines taken and the file's total length so the model knows it is not seeing everything.
+pub fn render_file_block(file: &ContextFile, loaded: &LoadedText) -> FileBlock {
+    let display = file.display_name();
+    let contents = loaded.text.as_str();
+    let labels: Vec<String> = file
+        .revision
+        .iter()
+        .map(|revision| format!("at git revision {revision}, not the working tree"))
+        .chain(file.origin.label().map(str::to_string))
+        .chain(loaded.encoding.label().map(str::to_string))
+        .collect();
+    let origin: String = labels.iter().map(|label| format!("{label}; ")).collect();
+
+    match &file.selection {
+        Selection::Whole => FileBlock {
+            text: if labels.is_empty() {
+                format!("\n\n===== FILE: {display} =====\n{contents}\n")
+            } else {
+                format!(
+                    "\n\n===== FILE: {display} ({}) =====\n{contents}\n",
+                    labels.join("; ")
+                )
+            },
+            problem: None,
+        },
+        Selection::Symbol(symbol) => {
+            let total = contents.lines().count();
+            match symbols::extract_symbol(file.inner_path(), contents, symbol) {
+                Ok(excerpts) => FileBlock {
+                    text: excerpts
+                        .iter()
+                        .map(|excerpt| {
+                            let (start, end) = excerpt.lines;
+                            format!(
+                                "\n\n===== FILE: {display}#{symbol} ({origin}lines {start}-{end} of {total}) =====\n{}",
+                                excerpt.text
+                            )
+                        })
+                        .collect(),
+                    problem: None,
+                },
+                Err(reason) => FileBlock {
+                    text: format!(
+                        "\n\n===== FILE: {display}#{symbol} (symbol not resolved) =====\n{reason}\n"
+                    ),
+                    problem: Some(reason),
+                },
+            }
+        }
+        selection => {
+            let excerpt = match loaded.streamed_lines {
+                Some((lines, total_lines)) => Excerpt {
+                    text: contents,
+                    lines,
+                    total_lines,
+                },
+                None => selection.apply(contents),
+            };
+            let total = excerpt.total_lines;
+            match excerpt.lines {
+                Some((start, end)) => FileBlock {
+                    text: format!(
+                        "\n\n===== FILE: {display} ({origin}lines {start}-{end} of {total}) =====\n{}\n",
+                        excerpt.text
+                    ),
+                    problem: None,
+                },
+                None => {
+                    let (start, end) = selection.line_bounds().unwrap_or_default();
+                    let reason = format!(
+                        "requested lines {start}-{end}, but the file has only {total} lines"
+                    );
+                    FileBlock {
+                        text: format!("\n\n===== FILE: {display} ({reason}) =====\n\n"),
+                        problem: Some(reason),
+                    }
+                }
+            }
+        }
+    }
+}
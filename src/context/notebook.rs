@@ -0,0 +1,220 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::floor_char_boundary;
+
+/// Notebooks are parsed whole, embedded outputs included, up to this size.
+pub const MAX_NOTEBOOK_BYTES: u64 = 64 * 1024 * 1024;
+/// Lines kept from each cell output; errors keep the end of their traceback instead.
+const MAX_OUTPUT_LINES: usize = 40;
+/// Bytes kept from each cell output.
+const MAX_OUTPUT_BYTES: usize = 4 * 1024;
+
+static ANSI_ESCAPE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").expect("valid regex"));
+
+pub fn is_notebook_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Renders an `.ipynb` (nbformat 4) as its cells in order: markdown and code sources with cell
+/// numbers and execution counts, text outputs and errors shortened, and rich outputs such as
+/// images replaced by placeholders.
+pub fn render_notebook(bytes: &[u8]) -> Result<String, String> {
+    let notebook: Value =
+        serde_json::from_slice(bytes).map_err(|err| format!("not valid notebook JSON: {err}"))?;
+    let format = notebook.get("nbformat").and_then(Value::as_u64);
+    if format != Some(4) {
+        return Err(format!(
+            "unsupported notebook format {}",
+            format.map_or_else(|| "(missing)".to_string(), |format| format.to_string())
+        ));
+    }
+    let cells = notebook
+        .get("cells")
+        .and_then(Value::as_array)
+        .ok_or_else(|| "notebook has no cells array".to_string())?;
+
+    let kernel = notebook
+        .pointer("/metadata/kernelspec/display_name")
+        .or_else(|| notebook.pointer("/metadata/language_info/name"))
+        .and_then(Value::as_str);
+    let mut text = format!(
+        "[Jupyter notebook: {} cells{}; rendered from the .ipynb JSON]\n",
+        cells.len(),
+        kernel.map_or_else(String::new, |kernel| format!(", kernel {kernel}"))
+    );
+
+    for (index, cell) in cells.iter().enumerate() {
+        let kind = cell
+            .get("cell_type")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        let execution = match cell.get("execution_count").and_then(Value::as_u64) {
+            Some(count) => format!(", In [{count}]"),
+            None if kind == "code" => ", not run".to_string(),
+            None => String::new(),
+        };
+        text.push_str(&format!(
+            "\n----- cell {} ({kind}{execution}) -----\n",
+            index + 1
+        ));
+        push_line_block(&mut text, &multiline(cell.get("source")));
+
+        for output in cell
+            .get("outputs")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            render_output(&mut text, output);
+        }
+    }
+    Ok(text)
+}
+
+fn render_output(text: &mut String, output: &Value) {
+    match output.get("output_type").and_then(Value::as_str) {
+        Some("stream") => {
+            let name = output
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("stdout");
+            text.push_str(&format!("[{name}]\n"));
+            push_line_block(text, &head(&multiline(output.get("text"))));
+        }
+        Some("execute_result" | "display_data") => {
+            let data = output.get("data");
+            let mut placeholders = Vec::new();
+            for (media_type, _) in data.and_then(Value::as_object).into_iter().flatten() {
+                if media_type != "text/plain" {
+                    placeholders.push(media_type.as_str());
+                }
+            }
+            match data.and_then(|data| data.get("text/plain")) {
+                Some(plain) => {
+                    text.push_str("[output]\n");
+                    push_line_block(text, &head(&multiline(Some(plain))));
+                }
+                None if placeholders.is_empty() => {}
+                None => text.push_str("[output]\n"),
+            }
+            if !placeholders.is_empty() {
+                text.push_str(&format!("[{} output omitted]\n", placeholders.join(", ")));
+            }
+        }
+        Some("error") => {
+            let name = output
+                .get("ename")
+                .and_then(Value::as_str)
+                .unwrap_or("Error");
+            let value = output
+                .get("evalue")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            text.push_str(&format!("[error] {name}: {value}\n"));
+            let traceback: Vec<String> = output
+                .get("traceback")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+            push_line_block(
+                text,
+                &tail(&ANSI_ESCAPE.replace_all(&traceback.join("\n"), "")),
+            );
+        }
+        _ => {}
+    }
+}
+
+/// Notebook text fields are either a string or a list of lines.
+fn multiline(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => ANSI_ESCAPE.replace_all(text, "").into_owned(),
+        Some(Value::Array(lines)) => {
+            let joined: String = lines.iter().filter_map(Value::as_str).collect();
+            ANSI_ESCAPE.replace_all(&joined, "").into_owned()
+        }
+        _ => String::new(),
+    }
+}
+
+fn push_line_block(text: &mut String, block: &str) {
+    if block.is_empty() {
+        return;
+    }
+    text.push_str(block);
+    if !block.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+/// The first lines of an output, within the line and byte limits.
+fn head(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut kept = String::new();
+    let mut count = 0;
+    for line in &lines {
+        if count == 0 && line.len() > MAX_OUTPUT_BYTES {
+            // One huge line (a printed array, say) still shows its start.
+            kept.push_str(&line[..floor_char_boundary(line, MAX_OUTPUT_BYTES)]);
+            kept.push_str("...[line cut]\n");
+            count += 1;
+            continue;
+        }
+        if count == MAX_OUTPUT_LINES || kept.len() + line.len() > MAX_OUTPUT_BYTES {
+            break;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+        count += 1;
+    }
+    if count < lines.len() {
+        kept.push_str(&format!(
+            "...[{} more output lines]...\n",
+            lines.len() - count
+        ));
+    }
+    kept
+}
+
+/// The last lines of an output, within the line and byte limits; tracebacks end with the cause.
+fn tail(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut start = lines.len();
+    let mut bytes = 0;
+    while start > 0
+        && lines.len() - start < MAX_OUTPUT_LINES
+        && bytes + lines[start - 1].len() <= MAX_OUTPUT_BYTES
+    {
+        start -= 1;
+        bytes += lines[start].len();
+    }
+    let mut kept = String::new();
+    if start == lines.len() && start > 0 {
+        // The last line alone is over the limit; it names the error, so show its start.
+        start -= 1;
+        if start > 0 {
+            kept.push_str(&format!("...[{start} earlier traceback lines]...\n"));
+        }
+        let line = lines[start];
+        kept.push_str(&line[..floor_char_boundary(line, MAX_OUTPUT_BYTES)]);
+        kept.push_str("...[line cut]\n");
+        return kept;
+    }
+    if start > 0 {
+        kept.push_str(&format!("...[{start} earlier traceback lines]...\n"));
+    }
+    for line in &lines[start..] {
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    kept
+}
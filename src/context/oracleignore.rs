@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// Name of the files listing paths to keep out of prompts, in `.gitignore` syntax.
+pub const ORACLEIGNORE: &str = ".oracleignore";
+
+/// The `.oracleignore` line that excluded a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ignored {
+    /// The `.oracleignore` file, relative to the readable root it lives in.
+    pub file: String,
+    pub pattern: String,
+}
+
+impl fmt::Display for Ignored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "excluded by {} (`{}`)", self.file, self.pattern)
+    }
+}
+
+/// Files and directories a walk skipped because of `.oracleignore`, relative to where it
+/// started. Directories end with `/` and are listed instead of their contents.
+pub type Pruned = Arc<Mutex<Vec<(String, Ignored)>>>;
+
+/// Lazily loaded `.oracleignore` files, keyed by the directory they sit in.
+#[derive(Debug, Default)]
+pub struct OracleIgnore {
+    files: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl OracleIgnore {
+    /// Checks `path` against the `.oracleignore` files in `top` and every directory between it
+    /// and the path. As with git, a file in a deeper directory overrides one above it, and a
+    /// later line overrides an earlier one, so `!keep.rs` can re-include a file. `top` and
+    /// `path` must be canonical. Blocking.
+    pub fn check(&self, top: &Path, path: &Path, is_dir: bool) -> Option<Ignored> {
+        let relative = path.strip_prefix(top).ok()?;
+        let mut dirs = vec![top.to_path_buf()];
+        if let Some(parent) = relative.parent() {
+            let mut dir = top.to_path_buf();
+            for component in parent.components() {
+                dir.push(component);
+                dirs.push(dir.clone());
+            }
+        }
+        for dir in dirs.iter().rev() {
+            let Some(gitignore) = self.load(dir) else {
+                continue;
+            };
+            match gitignore.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(glob) => {
+                    let file = dir.join(ORACLEIGNORE);
+                    return Some(Ignored {
+                        file: file
+                            .strip_prefix(top)
+                            .unwrap_or(&file)
+                            .to_string_lossy()
+                            .into_owned(),
+                        pattern: glob.original().to_string(),
+                    });
+                }
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+        None
+    }
+
+    /// The `.oracleignore` in `dir`, if there is one. Lines that don't parse are skipped.
+    fn load(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut files = self.files.lock().unwrap_or_else(|err| err.into_inner());
+        files
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let file = dir.join(ORACLEIGNORE);
+                if !file.is_file() {
+                    return None;
+                }
+                let mut builder = GitignoreBuilder::new(dir);
+                let _ = builder.add(&file);
+                builder.build().ok().map(Arc::new)
+            })
+            .clone()
+    }
+}
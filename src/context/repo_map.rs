@@ -0,0 +1,91 @@
+use ignore::WalkBuilder;
+
+use super::oracleignore::ORACLEIGNORE;
+use super::roots::Roots;
+use super::symbols::{Lang, outline};
+
+/// Upper bound on the size of the rendered repository map.
+pub const MAX_REPO_MAP_BYTES: usize = 32 * 1024;
+/// Files larger than this are listed but not parsed for signatures.
+const MAX_OUTLINE_FILE_BYTES: u64 = 512 * 1024;
+
+/// Builds a compact outline of the working tree rooted at the base directory of `roots`: the
+/// directory structure plus the top-level signatures of every Rust, TypeScript/JavaScript,
+/// Python and Go file.
+///
+/// The walk honors `.gitignore` and `.oracleignore`, skips hidden files and leaves out anything
+/// the sandbox refuses, names included. Entries are sorted so the map is stable between calls.
+/// Output stops before it would pass [`MAX_REPO_MAP_BYTES`], mid-outline if need be, with a
+/// note counting the files left out. Blocking.
+pub fn build_repo_map(roots: &Roots) -> String {
+    let mut map = String::new();
+    let mut full = false;
+    let mut skipped_files = 0usize;
+
+    let sandbox = roots.sandbox().clone();
+    let walker = WalkBuilder::new(roots.base())
+        .require_git(false)
+        .add_custom_ignore_filename(ORACLEIGNORE)
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .filter_entry(move |dent| dent.depth() == 0 || sandbox.check(dent.path()).is_ok())
+        .build();
+
+    for dent in walker.filter_map(Result::ok) {
+        let depth = dent.depth();
+        if depth == 0 {
+            continue;
+        }
+        let is_file = dent.file_type().is_some_and(|kind| kind.is_file());
+        let indent = "  ".repeat(depth - 1);
+        let name = dent.file_name().to_string_lossy();
+        let entry = if is_file {
+            format!("{indent}{name}\n")
+        } else {
+            format!("{indent}{name}/\n")
+        };
+        full = full || map.len() + entry.len() > MAX_REPO_MAP_BYTES;
+        if full {
+            if is_file {
+                skipped_files += 1;
+            }
+            continue;
+        }
+        map.push_str(&entry);
+        if !is_file {
+            continue;
+        }
+
+        let Some(lang) = Lang::from_path(dent.path()) else {
+            continue;
+        };
+        let small_enough = dent
+            .metadata()
+            .is_ok_and(|meta| meta.len() <= MAX_OUTLINE_FILE_BYTES);
+        if !small_enough {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(dent.path()) else {
+            continue;
+        };
+        for line in outline(lang, &source) {
+            let line = format!("{indent}  {line}\n");
+            if map.len() + line.len() > MAX_REPO_MAP_BYTES {
+                full = true;
+                break;
+            }
+            map.push_str(&line);
+        }
+    }
+
+    if skipped_files > 0 {
+        map.push_str(&format!(
+            "... [repository map truncated at {MAX_REPO_MAP_BYTES} bytes; {skipped_files} more file(s) not shown]\n"
+        ));
+    } else if full {
+        map.push_str(&format!(
+            "... [repository map truncated at {MAX_REPO_MAP_BYTES} bytes]\n"
+        ));
+    }
+
+    map
+}
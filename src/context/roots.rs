@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ignore::WalkBuilder;
+
+use super::oracleignore::{Ignored, OracleIgnore, Pruned};
+use super::sandbox::{Sandbox, SandboxViolation, resolve_path};
+
+/// Directories that `files` entries resolve against: the working directory or the workspace
+/// roots the MCP client listed, plus named roots that an entry selects with a `NAME:` prefix
+/// (`client:src/api.ts`). Every file is checked against the sandbox before it is read, and
+/// against the `.oracleignore` files of the readable root it lives in.
+#[derive(Debug, Clone)]
+pub struct Roots {
+    base: PathBuf,
+    /// Canonical workspace roots from the client's `roots/list`.
+    workspace: Vec<PathBuf>,
+    named: BTreeMap<String, PathBuf>,
+    sandbox: Sandbox,
+    oracleignore: Arc<OracleIgnore>,
+}
+
+impl Roots {
+    /// Roots for the client's workspace, or the working directory when `workspace` is empty.
+    /// Relative entries resolve against the first workspace root, or against a later one that
+    /// has the entry's first path component. Blocking.
+    pub fn new(workspace: &[PathBuf], sandbox: Sandbox) -> Self {
+        let workspace: Vec<PathBuf> = workspace
+            .iter()
+            .map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.clone()))
+            .collect();
+        Self {
+            base: workspace
+                .first()
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from(".")),
+            workspace,
+            named: BTreeMap::new(),
+            sandbox,
+            oracleignore: Arc::default(),
+        }
+    }
+
+    /// Adds the request's named roots. Names are letters, digits, `-` and `_`, at least two
+    /// characters long so they can't be mistaken for a Windows drive; every root must be an
+    /// existing directory. Relative roots resolve against the base directory. Untrusted roots
+    /// must lie inside the sandbox; `trusted` ones (the local user's own `--root`) widen it.
+    pub fn with_named(
+        mut self,
+        named: &HashMap<String, String>,
+        trusted: bool,
+    ) -> Result<Self, String> {
+        for (name, dir) in named {
+            if name.len() < 2
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+            {
+                return Err(format!(
+                    "Invalid root name `{name}`: use at least two letters, digits, `-` or `_`"
+                ));
+            }
+            let path = if self.base == Path::new(".") {
+                PathBuf::from(dir)
+            } else {
+                self.base.join(dir)
+            };
+            if !path.is_dir() {
+                return Err(format!("Root `{name}` ({dir}) is not a directory"));
+            }
+            if trusted {
+                self.sandbox.allow(&path);
+            } else if !self.sandbox.contains(&path) {
+                return Err(format!(
+                    "Root `{name}` ({dir}) is outside the readable roots"
+                ));
+            }
+            self.named.insert(name.clone(), path);
+        }
+        Ok(self)
+    }
+
+    /// Splits a `NAME:` prefix naming a known root off `entry`. Returns the directory to resolve
+    /// the rest against, the root name if there was one, and the rest of the entry.
+    pub fn resolve<'a>(&self, entry: &'a str) -> (&Path, Option<&str>, &'a str) {
+        if let Some((name, rest)) = entry.split_once(':')
+            && let Some((name, dir)) = self.named.get_key_value(name)
+        {
+            return (dir, Some(name), rest);
+        }
+        (self.workspace_for(entry), None, entry)
+    }
+
+    /// The directory that relative paths outside any entry (stack frames, auto-context, git)
+    /// resolve against.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Checks `path` against the sandbox. Blocking.
+    pub fn check(&self, path: &Path) -> Result<(), SandboxViolation> {
+        self.sandbox.check(path)
+    }
+
+    /// Whether a `.oracleignore` excludes `path`. Paths that don't exist are judged by where
+    /// they would be. Blocking.
+    pub fn ignored(&self, path: &Path) -> Option<Ignored> {
+        let resolved = resolve_path(path);
+        let top = self.sandbox.root_of(&resolved)?;
+        self.oracleignore.check(top, &resolved, resolved.is_dir())
+    }
+
+    /// Makes `walker`, which starts at `dir`, skip what `.oracleignore` files exclude, and
+    /// returns the list the skipped entries are recorded in. `dir` itself is not checked; use
+    /// [`Roots::ignored`] for that. Blocking.
+    pub fn prune_ignored(&self, walker: &mut WalkBuilder, dir: &Path) -> Pruned {
+        let pruned = Pruned::default();
+        let Ok(start) = dir.canonicalize() else {
+            return pruned;
+        };
+        let Some(top) = self.sandbox.root_of(&start).map(Path::to_path_buf) else {
+            return pruned;
+        };
+        let dir = dir.to_path_buf();
+        let oracleignore = Arc::clone(&self.oracleignore);
+        let recorded = Arc::clone(&pruned);
+        walker.filter_entry(move |dent| {
+            let Ok(relative) = dent.path().strip_prefix(&dir) else {
+                return true;
+            };
+            let is_dir = dent.file_type().is_some_and(|kind| kind.is_dir());
+            let Some(ignored) = oracleignore.check(&top, &start.join(relative), is_dir) else {
+                return true;
+            };
+            let mut display = relative.to_string_lossy().into_owned();
+            if is_dir {
+                display.push('/');
+            }
+            recorded
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push((display, ignored));
+            false
+        });
+        pruned
+    }
+
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.sandbox
+    }
+
+    pub fn workspace(&self) -> &[PathBuf] {
+        &self.workspace
+    }
+
+    pub fn named(&self) -> impl Iterator<Item = (&str, &Path)> {
+        self.named
+            .iter()
+            .map(|(name, dir)| (name.as_str(), dir.as_path()))
+    }
+
+    /// The first workspace root holding the entry's first path component, falling back to the
+    /// base directory.
+    fn workspace_for(&self, entry: &str) -> &Path {
+        if self.workspace.len() < 2 {
+            return &self.base;
+        }
+        let first = entry
+            .trim_start_matches("./")
+            .split(['/', '\\'])
+            .next()
+            .unwrap_or_default();
+        if first.is_empty() || first == ".." || first.contains(['*', '?', '[', '{']) {
+            return &self.base;
+        }
+        self.workspace
+            .iter()
+            .find(|root| root.join(first).exists())
+            .unwrap_or(&self.base)
+    }
+}
@@ -0,0 +1,432 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+
+/// Files that are never read unless the config file says otherwise: environment files, keys
+/// and certificates, SSH keys and credential stores.
+pub const DEFAULT_DENY: &[&str] = &[
+    ".env*",
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "id_*",
+    ".netrc",
+    ".pgpass",
+    ".git-credentials",
+];
+
+/// Why the sandbox refused a path. Shown to the model and the client in place of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SandboxViolation {
+    /// The path lies outside every readable root.
+    OutsideRoots,
+    /// `..` components lead out of the readable roots.
+    Traversal,
+    /// The path is inside a readable root but is a symlink, or goes through one, that points out.
+    SymlinkEscape { target: PathBuf },
+    /// The path matches a deny pattern.
+    Denied { pattern: String },
+}
+
+impl fmt::Display for SandboxViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxViolation::OutsideRoots => {
+                write!(f, "blocked by the sandbox: outside the readable roots")
+            }
+            SandboxViolation::Traversal => {
+                write!(
+                    f,
+                    "blocked by the sandbox: `..` leads outside the readable roots"
+                )
+            }
+            SandboxViolation::SymlinkEscape { target } => write!(
+                f,
+                "blocked by the sandbox: symlink to {} outside the readable roots",
+                target.display()
+            ),
+            SandboxViolation::Denied { pattern } => {
+                write!(
+                    f,
+                    "blocked by the sandbox: matches deny pattern `{pattern}`"
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DenyPattern {
+    pattern: String,
+    matcher: GlobMatcher,
+    /// Patterns without a `/` match any single path component, so `.env*` also covers
+    /// `config/.env.local` and `id_*` covers `.ssh/id_ed25519`.
+    component: bool,
+}
+
+/// Read policy for everything that ends up in a prompt: files must resolve, after following
+/// symlinks and `..`, to somewhere inside a readable root, and must not match a deny pattern.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    /// Canonical readable directories.
+    roots: Vec<PathBuf>,
+    deny: Vec<DenyPattern>,
+}
+
+impl Sandbox {
+    /// A sandbox over `roots` (missing ones are skipped) with the given deny globs. Blocking.
+    pub fn new(roots: &[PathBuf], deny: &[String]) -> Result<Self, String> {
+        let mut sandbox = Self {
+            roots: Vec::new(),
+            deny: Vec::new(),
+        };
+        for root in roots {
+            sandbox.allow(root);
+        }
+        for pattern in deny {
+            let matcher = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| format!("Invalid `[sandbox] deny` pattern `{pattern}`: {err}"))?
+                .compile_matcher();
+            sandbox.deny.push(DenyPattern {
+                pattern: pattern.clone(),
+                matcher,
+                component: !pattern.contains('/'),
+            });
+        }
+        Ok(sandbox)
+    }
+
+    /// Makes `dir` readable too. Blocking.
+    pub fn allow(&mut self, dir: &Path) {
+        if let Ok(dir) = dir.canonicalize()
+            && !self.roots.contains(&dir)
+        {
+            self.roots.push(dir);
+        }
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.root_of(&resolve_path(path)).is_some()
+    }
+
+    /// Checks that `path` may be read. Paths that don't exist are judged by where they would
+    /// be. Blocking.
+    pub fn check(&self, path: &Path) -> Result<(), SandboxViolation> {
+        let resolved = resolve_path(path);
+        let Some(root) = self.root_of(&resolved) else {
+            let lexical = normalize(path);
+            return Err(if self.root_of(&lexical).is_some() {
+                SandboxViolation::SymlinkEscape { target: resolved }
+            } else if path
+                .components()
+                .any(|component| component == Component::ParentDir)
+            {
+                SandboxViolation::Traversal
+            } else {
+                SandboxViolation::OutsideRoots
+            });
+        };
+        // Deny patterns see the path as resolved and, for symlinks, the name it was asked by.
+        let inside = resolved.strip_prefix(root).unwrap_or(&resolved);
+        self.check_relative(inside)?;
+        match path.file_name() {
+            Some(name) => self.check_relative(Path::new(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a path inside an archive against the deny patterns.
+    pub fn check_member(&self, member: &str) -> Result<(), SandboxViolation> {
+        self.check_relative(Path::new(member))
+    }
+
+    /// `git diff` pathspecs that keep denied files out of diffs.
+    pub fn git_excludes(&self) -> Vec<String> {
+        self.deny
+            .iter()
+            .map(|deny| {
+                if deny.component {
+                    format!(":(exclude,glob)**/{}", deny.pattern)
+                } else {
+                    format!(":(exclude,glob){}", deny.pattern)
+                }
+            })
+            .collect()
+    }
+
+    /// The readable root that holds `path`, which must already be resolved.
+    pub(super) fn root_of(&self, path: &Path) -> Option<&Path> {
+        self.roots
+            .iter()
+            .find(|root| path.starts_with(root))
+            .map(PathBuf::as_path)
+    }
+
+    fn check_relative(&self, relative: &Path) -> Result<(), SandboxViolation> {
+        let denied = self.deny.iter().find(|deny| {
+            if deny.component {
+                relative.components().any(|component| match component {
+                    Component::Normal(name) => deny.matcher.is_match(name),
+                    _ => false,
+                })
+            } else {
+                deny.matcher.is_match(relative)
+            }
+        });
+        match denied {
+            Some(deny) => Err(SandboxViolation::Denied {
+                pattern: deny.pattern.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `path` made absolute against the working directory, with `.` and `..` folded without
+/// touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Canonical form of `path`; for a path that doesn't exist, the canonical form of its nearest
+/// existing ancestor with the rest of the path applied lexically.
+pub(super) fn resolve_path(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(mut resolved) = existing.canonicalize() {
+            for component in missing.iter().rev() {
+                match component {
+                    Component::ParentDir => {
+                        resolved.pop();
+                    }
+                    Component::Normal(name) => resolved.push(name),
+                    _ => {}
+                }
+            }
+            return resolved;
+        }
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(last)) if existing != Path::new(".") => {
+                missing.push(last);
+                existing = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                };
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("oracle-sandbox-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir.canonicalize().unwrap())
+        }
+
+        fn file(&self, relative: &str) -> PathBuf {
+            let path = self.0.join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "x").unwrap();
+            path
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn default_deny() -> Vec<String> {
+        DEFAULT_DENY
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn files_inside_the_roots_are_readable() {
+        let scratch = Scratch::new("inside");
+        let workspace = scratch.0.join("ws");
+        let file = scratch.file("ws/src/main.rs");
+        let sandbox = Sandbox::new(std::slice::from_ref(&workspace), &default_deny()).unwrap();
+
+        assert_eq!(sandbox.check(&file), Ok(()));
+        // Missing files are judged by where they would be.
+        assert_eq!(sandbox.check(&workspace.join("src/new.rs")), Ok(()));
+        assert_eq!(sandbox.check(&workspace.join("src/../src/main.rs")), Ok(()));
+        assert!(sandbox.contains(&workspace.join("src")));
+    }
+
+    #[test]
+    fn traversal_and_outside_paths_are_refused() {
+        let scratch = Scratch::new("traversal");
+        let workspace = scratch.0.join("ws");
+        scratch.file("ws/src/main.rs");
+        scratch.file("outside/notes.txt");
+        let sandbox = Sandbox::new(std::slice::from_ref(&workspace), &[]).unwrap();
+
+        assert_eq!(
+            sandbox.check(&workspace.join("../outside/notes.txt")),
+            Err(SandboxViolation::Traversal)
+        );
+        assert_eq!(
+            sandbox.check(&workspace.join("src/../../outside/missing.txt")),
+            Err(SandboxViolation::Traversal)
+        );
+        assert_eq!(
+            sandbox.check(&scratch.0.join("outside/notes.txt")),
+            Err(SandboxViolation::OutsideRoots)
+        );
+        assert!(!sandbox.contains(&scratch.0.join("outside")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_pointing_out_are_refused() {
+        let scratch = Scratch::new("symlink");
+        let workspace = scratch.0.join("ws");
+        scratch.file("ws/src/main.rs");
+        let secret = scratch.file("outside/secret.txt");
+        std::os::unix::fs::symlink(&secret, workspace.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(scratch.0.join("outside"), workspace.join("linked")).unwrap();
+        std::os::unix::fs::symlink(workspace.join("src/main.rs"), workspace.join("ok.rs")).unwrap();
+        let sandbox = Sandbox::new(std::slice::from_ref(&workspace), &[]).unwrap();
+
+        assert_eq!(
+            sandbox.check(&workspace.join("link.txt")),
+            Err(SandboxViolation::SymlinkEscape {
+                target: secret.clone()
+            })
+        );
+        assert_eq!(
+            sandbox.check(&workspace.join("linked/secret.txt")),
+            Err(SandboxViolation::SymlinkEscape { target: secret })
+        );
+        assert_eq!(sandbox.check(&workspace.join("ok.rs")), Ok(()));
+    }
+
+    #[test]
+    fn deny_patterns_without_a_slash_match_any_component() {
+        let scratch = Scratch::new("deny");
+        let workspace = scratch.0.join("ws");
+        let deny = |pattern: &str| {
+            Err(SandboxViolation::Denied {
+                pattern: pattern.to_string(),
+            })
+        };
+        std::fs::create_dir_all(&workspace).unwrap();
+        let mut patterns = default_deny();
+        patterns.push("fixtures/secrets/**".to_string());
+        let sandbox = Sandbox::new(std::slice::from_ref(&workspace), &patterns).unwrap();
+
+        assert_eq!(sandbox.check(&scratch.file("ws/.env")), deny(".env*"));
+        assert_eq!(
+            sandbox.check(&scratch.file("ws/config/.env.local")),
+            deny(".env*")
+        );
+        assert_eq!(
+            sandbox.check(&scratch.file("ws/.ssh/id_ed25519")),
+            deny("id_*")
+        );
+        assert_eq!(
+            sandbox.check(&scratch.file("ws/certs/server.pem")),
+            deny("*.pem")
+        );
+        assert_eq!(
+            sandbox.check(&scratch.file("ws/fixtures/secrets/a.json")),
+            deny("fixtures/secrets/**")
+        );
+        // Patterns with a `/` are anchored at the root.
+        assert_eq!(
+            sandbox.check(&scratch.file("ws/test/fixtures/secrets/a.json")),
+            Ok(())
+        );
+        assert_eq!(sandbox.check(&scratch.file("ws/src/env.rs")), Ok(()));
+        assert_eq!(sandbox.check(&scratch.file("ws/src/key.rs")), Ok(()));
+
+        assert_eq!(sandbox.check_member("config/.env"), deny(".env*"));
+        assert_eq!(sandbox.check_member("src/lib.rs"), Ok(()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deny_patterns_see_the_name_a_symlink_was_asked_by() {
+        let scratch = Scratch::new("deny-symlink");
+        let workspace = scratch.0.join("ws");
+        let env = scratch.file("ws/.env");
+        std::os::unix::fs::symlink(&env, workspace.join("settings.txt")).unwrap();
+        let plain = scratch.file("ws/plain.txt");
+        std::os::unix::fs::symlink(&plain, workspace.join("server.pem")).unwrap();
+        let sandbox = Sandbox::new(std::slice::from_ref(&workspace), &default_deny()).unwrap();
+
+        assert_eq!(
+            sandbox.check(&workspace.join("settings.txt")),
+            Err(SandboxViolation::Denied {
+                pattern: ".env*".to_string()
+            })
+        );
+        assert_eq!(
+            sandbox.check(&workspace.join("server.pem")),
+            Err(SandboxViolation::Denied {
+                pattern: "*.pem".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn git_excludes_anchor_component_patterns_anywhere() {
+        let sandbox = Sandbox::new(
+            &[],
+            &[".env*".to_string(), "fixtures/secrets/**".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            sandbox.git_excludes(),
+            vec![
+                ":(exclude,glob)**/.env*".to_string(),
+                ":(exclude,glob)fixtures/secrets/**".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_deny_patterns_are_an_error() {
+        let err = Sandbox::new(&[], &["[".to_string()]).unwrap_err();
+        assert!(err.contains("`[`"), "{err}");
+    }
+}
@@ -0,0 +1,345 @@
+/// Part of a file a `files` entry asks for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Selection {
+    Whole,
+    /// `path:START-END`, 1-based and inclusive.
+    Lines {
+        start: usize,
+        end: usize,
+    },
+    /// `path:LINE±N` (or `path:LINE+-N`), the line plus `radius` lines on either side.
+    Around {
+        line: usize,
+        radius: usize,
+    },
+    /// `path#Name` or `path#Type::method`, a definition located with tree-sitter.
+    Symbol(String),
+}
+
+/// A parsed `files` entry: the path (or glob), the git revision to read it at, the archive member
+/// it names, and the part of the file to include.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSpec {
+    pub path: String,
+    pub selection: Selection,
+    /// `path@REV`: read the file from git history instead of the working tree.
+    pub revision: Option<String>,
+    /// `archive.zip!/path/in/zip`: a member (or a glob or directory of members) of the archive at
+    /// `path`.
+    pub member: Option<String>,
+}
+
+impl FileSpec {
+    /// Splits a trailing `#Symbol`, `:START-END` or `:LINE±N` suffix off an entry, then an
+    /// `@REV` suffix off the path (`src/lib.rs@v0.1.0#parse`), then an archive member
+    /// (`vendor.tar.gz!/src/lib.rs`). Entries without a well-formed suffix are taken as plain
+    /// paths, so files whose names contain `:` or `#` still work.
+    pub fn parse(entry: &str) -> Self {
+        let (path, selection) = split_selection(entry);
+        let (path, revision) = split_revision(path);
+        let (path, member) = match path.split_once("!/") {
+            Some((archive, member)) if !archive.is_empty() => (archive, Some(member)),
+            _ => (path, None),
+        };
+        Self {
+            path: path.to_string(),
+            selection,
+            revision: revision.map(str::to_string),
+            member: member.map(str::to_string),
+        }
+    }
+
+    /// The path that tells the file's type: the member's path for archive members.
+    pub fn inner_path(&self) -> &str {
+        self.member.as_deref().unwrap_or(&self.path)
+    }
+}
+
+fn split_selection(entry: &str) -> (&str, Selection) {
+    if let Some((path, symbol)) = entry.rsplit_once('#')
+        && !path.is_empty()
+        && is_symbol_path(symbol)
+    {
+        return (path, Selection::Symbol(symbol.to_string()));
+    }
+
+    if let Some((path, suffix)) = entry.rsplit_once(':')
+        && !path.is_empty()
+        && let Some(selection) = parse_selection(suffix)
+    {
+        return (path, selection);
+    }
+
+    (entry, Selection::Whole)
+}
+
+/// Splits `path@REV`. An `@` right after a `/` starts a directory name (`node_modules/@types`),
+/// not a revision.
+fn split_revision(path: &str) -> (&str, Option<&str>) {
+    match path.rsplit_once('@') {
+        Some((file, revision))
+            if !file.is_empty() && !file.ends_with('/') && is_revision(revision) =>
+        {
+            (file, Some(revision))
+        }
+        _ => (path, None),
+    }
+}
+
+fn is_revision(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with(['-', '/', '.'])
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | '~' | '^'))
+}
+
+fn parse_selection(suffix: &str) -> Option<Selection> {
+    if let Some((line, radius)) = suffix.split_once('±').or_else(|| suffix.split_once("+-")) {
+        let line = parse_line_number(line)?;
+        let radius = radius.parse().ok()?;
+        return Some(Selection::Around { line, radius });
+    }
+
+    let (start, end) = suffix.split_once('-')?;
+    let start = parse_line_number(start)?;
+    let end = parse_line_number(end)?;
+    (start <= end).then_some(Selection::Lines { start, end })
+}
+
+fn is_symbol_path(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$')
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '$' | ':' | '.'))
+}
+
+fn parse_line_number(text: &str) -> Option<usize> {
+    text.parse().ok().filter(|line| *line > 0)
+}
+
+/// The lines of a file actually included in the prompt.
+#[derive(Debug, Clone)]
+pub struct Excerpt<'a> {
+    pub text: &'a str,
+    /// First and last included line (1-based); `None` when the range is past the end of the file.
+    pub lines: Option<(usize, usize)>,
+    pub total_lines: usize,
+}
+
+impl Selection {
+    /// Line range to keep, before clamping to the file length.
+    pub fn line_bounds(&self) -> Option<(usize, usize)> {
+        match *self {
+            Selection::Whole | Selection::Symbol(_) => None,
+            Selection::Lines { start, end } => Some((start, end)),
+            Selection::Around { line, radius } => Some((
+                line.saturating_sub(radius).max(1),
+                line.saturating_add(radius),
+            )),
+        }
+    }
+
+    pub fn apply<'a>(&self, contents: &'a str) -> Excerpt<'a> {
+        let total_lines = contents.lines().count();
+        let Some((start, end)) = self.line_bounds() else {
+            return Excerpt {
+                text: contents,
+                lines: Some((1, total_lines)),
+                total_lines,
+            };
+        };
+
+        if start > total_lines {
+            return Excerpt {
+                text: "",
+                lines: None,
+                total_lines,
+            };
+        }
+        let end = end.min(total_lines);
+
+        let mut offsets = contents
+            .match_indices('\n')
+            .map(|(idx, _)| idx + 1)
+            .collect::<Vec<_>>();
+        offsets.insert(0, 0);
+        let from = offsets[start - 1];
+        let to = offsets.get(end).copied().unwrap_or(contents.len());
+
+        Excerpt {
+            text: &contents[from..to],
+            lines: Some((start, end)),
+            total_lines,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(
+        path: &str,
+        selection: Selection,
+        revision: Option<&str>,
+        member: Option<&str>,
+    ) -> FileSpec {
+        FileSpec {
+            path: path.to_string(),
+            selection,
+            revision: revision.map(str::to_string),
+            member: member.map(str::to_string),
+        }
+    }
+
+    fn lines(start: usize, end: usize) -> Selection {
+        Selection::Lines { start, end }
+    }
+
+    fn around(line: usize, radius: usize) -> Selection {
+        Selection::Around { line, radius }
+    }
+
+    fn symbol(name: &str) -> Selection {
+        Selection::Symbol(name.to_string())
+    }
+
+    #[test]
+    fn parses_entries() {
+        use Selection::Whole;
+        let cases = [
+            ("src/lib.rs", spec("src/lib.rs", Whole, None, None)),
+            (
+                "src/lib.rs:10-20",
+                spec("src/lib.rs", lines(10, 20), None, None),
+            ),
+            (
+                "src/lib.rs:7-7",
+                spec("src/lib.rs", lines(7, 7), None, None),
+            ),
+            (
+                "src/lib.rs:42±5",
+                spec("src/lib.rs", around(42, 5), None, None),
+            ),
+            (
+                "src/lib.rs:42+-5",
+                spec("src/lib.rs", around(42, 5), None, None),
+            ),
+            (
+                "src/lib.rs#parse",
+                spec("src/lib.rs", symbol("parse"), None, None),
+            ),
+            (
+                "src/lib.rs#Parser::parse",
+                spec("src/lib.rs", symbol("Parser::parse"), None, None),
+            ),
+            (
+                "app.ts#$store",
+                spec("app.ts", symbol("$store"), None, None),
+            ),
+            (
+                "src/lib.rs@HEAD~2",
+                spec("src/lib.rs", Whole, Some("HEAD~2"), None),
+            ),
+            (
+                "src/lib.rs@v0.1.0#parse",
+                spec("src/lib.rs", symbol("parse"), Some("v0.1.0"), None),
+            ),
+            (
+                "src/lib.rs@origin/main:1-5",
+                spec("src/lib.rs", lines(1, 5), Some("origin/main"), None),
+            ),
+            (
+                "vendor.tar.gz!/src/lib.rs",
+                spec("vendor.tar.gz", Whole, None, Some("src/lib.rs")),
+            ),
+            (
+                "vendor.zip!/src/lib.rs:3-4",
+                spec("vendor.zip", lines(3, 4), None, Some("src/lib.rs")),
+            ),
+            // The `NAME:` root prefix stays on the path; `Roots::resolve` strips it.
+            (
+                "web:src/a.ts@HEAD:1-5",
+                spec("web:src/a.ts", lines(1, 5), Some("HEAD"), None),
+            ),
+            ("web:src/a.ts", spec("web:src/a.ts", Whole, None, None)),
+        ];
+        for (entry, expected) in cases {
+            assert_eq!(FileSpec::parse(entry), expected, "{entry}");
+        }
+    }
+
+    #[test]
+    fn malformed_suffixes_are_part_of_the_path() {
+        let cases = [
+            "src/lib.rs:0-5",
+            "src/lib.rs:9-3",
+            "src/lib.rs:12",
+            "src/lib.rs:a-b",
+            "src/lib.rs:5±x",
+            "notes#1.md",
+            "issue#",
+            "#parse",
+            ":1-5",
+            "!/src/lib.rs",
+            "user@",
+            "src/lib.rs@-rf",
+            "src/lib.rs@../x",
+            "node_modules/@types/node/index.d.ts",
+            "C:\\src\\main.rs",
+            "file:name.txt",
+        ];
+        for entry in cases {
+            assert_eq!(
+                FileSpec::parse(entry),
+                spec(entry, Selection::Whole, None, None),
+                "{entry}"
+            );
+        }
+    }
+
+    #[test]
+    fn at_signs_and_drive_letters_combine_with_suffixes() {
+        assert_eq!(
+            FileSpec::parse("node_modules/@types/node/index.d.ts@HEAD:1-5"),
+            spec(
+                "node_modules/@types/node/index.d.ts",
+                lines(1, 5),
+                Some("HEAD"),
+                None
+            )
+        );
+        // Parsing alone can't tell `icon@2x.png` from a revision; `expand_entries` keeps the `@`
+        // when a file with the whole name exists.
+        assert_eq!(
+            FileSpec::parse("assets/icon@2x.png:3-4"),
+            spec("assets/icon", lines(3, 4), Some("2x.png"), None)
+        );
+        assert_eq!(
+            FileSpec::parse("C:\\src\\main.rs:10-20"),
+            spec("C:\\src\\main.rs", lines(10, 20), None, None)
+        );
+        assert_eq!(
+            FileSpec::parse("Makefile@v1.2"),
+            spec("Makefile", Selection::Whole, Some("v1.2"), None)
+        );
+    }
+
+    #[test]
+    fn inner_path_is_the_member_for_archives() {
+        assert_eq!(
+            FileSpec::parse("docs.zip!/guide/intro.md").inner_path(),
+            "guide/intro.md"
+        );
+        assert_eq!(FileSpec::parse("README.md:1-3").inner_path(), "README.md");
+    }
+
+    #[test]
+    fn line_bounds_clamp_at_the_first_line() {
+        assert_eq!(around(3, 10).line_bounds(), Some((1, 13)));
+        assert_eq!(lines(4, 8).line_bounds(), Some((4, 8)));
+        assert_eq!(Selection::Whole.line_bounds(), None);
+        assert_eq!(symbol("main").line_bounds(), None);
+    }
+}
@@ -0,0 +1,428 @@
+use std::path::Path;
+
+use tree_sitter::{Node, Parser, Tree};
+
+/// Languages Oracle can parse for symbol references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Rust,
+    TypeScript,
+    Tsx,
+    Python,
+    Go,
+}
+
+impl Lang {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rs" => Some(Lang::Rust),
+            "ts" | "mts" | "cts" => Some(Lang::TypeScript),
+            "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(Lang::Tsx),
+            "py" | "pyi" => Some(Lang::Python),
+            "go" => Some(Lang::Go),
+            _ => None,
+        }
+    }
+
+    fn grammar(self) -> tree_sitter::Language {
+        match self {
+            Lang::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Lang::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Lang::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Lang::Python => tree_sitter_python::LANGUAGE.into(),
+            Lang::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// Node kinds that define a named item.
+    fn is_definition(self, kind: &str) -> bool {
+        match self {
+            Lang::Rust => matches!(
+                kind,
+                "function_item"
+                    | "function_signature_item"
+                    | "struct_item"
+                    | "enum_item"
+                    | "union_item"
+                    | "trait_item"
+                    | "type_item"
+                    | "const_item"
+                    | "static_item"
+                    | "mod_item"
+                    | "macro_definition"
+            ),
+            Lang::TypeScript | Lang::Tsx => matches!(
+                kind,
+                "function_declaration"
+                    | "generator_function_declaration"
+                    | "class_declaration"
+                    | "abstract_class_declaration"
+                    | "interface_declaration"
+                    | "type_alias_declaration"
+                    | "enum_declaration"
+                    | "internal_module"
+                    | "method_definition"
+                    | "method_signature"
+                    | "abstract_method_signature"
+                    | "public_field_definition"
+                    | "variable_declarator"
+            ),
+            Lang::Python => matches!(kind, "function_definition" | "class_definition"),
+            Lang::Go => matches!(
+                kind,
+                "function_declaration"
+                    | "method_declaration"
+                    | "type_spec"
+                    | "type_alias"
+                    | "const_spec"
+                    | "var_spec"
+            ),
+        }
+    }
+
+    /// Node kinds whose header should be shown around a nested item (`impl X {`, `class Y:`).
+    fn is_container(self, kind: &str) -> bool {
+        match self {
+            Lang::Rust => matches!(kind, "impl_item" | "trait_item" | "mod_item"),
+            Lang::TypeScript | Lang::Tsx => matches!(
+                kind,
+                "class_declaration"
+                    | "abstract_class_declaration"
+                    | "interface_declaration"
+                    | "internal_module"
+            ),
+            Lang::Python => kind == "class_definition",
+            Lang::Go => false,
+        }
+    }
+
+    /// Wrapper nodes that belong to the item they wrap (`export`, decorators, `const x = ...`).
+    fn is_wrapper(self, kind: &str) -> bool {
+        match self {
+            Lang::Rust => false,
+            Lang::TypeScript | Lang::Tsx => {
+                matches!(
+                    kind,
+                    "export_statement" | "lexical_declaration" | "variable_declaration"
+                )
+            }
+            Lang::Python => kind == "decorated_definition",
+            Lang::Go => matches!(
+                kind,
+                "type_declaration" | "const_declaration" | "var_declaration"
+            ),
+        }
+    }
+
+    fn is_leading_comment(self, kind: &str) -> bool {
+        match self {
+            Lang::Rust => matches!(kind, "line_comment" | "block_comment" | "attribute_item"),
+            _ => kind == "comment",
+        }
+    }
+
+    fn uses_braces(self) -> bool {
+        self != Lang::Python
+    }
+
+    fn elision(self) -> &'static str {
+        match self {
+            Lang::Python => "# ...",
+            _ => "// ...",
+        }
+    }
+}
+
+/// A definition matched by a `path#Symbol` reference, rendered for the prompt.
+#[derive(Debug, Clone)]
+pub struct SymbolExcerpt {
+    /// The item with its doc comments, wrapped in the headers of its enclosing impl/class blocks.
+    pub text: String,
+    /// First and last line (1-based) of the item itself, including doc comments.
+    pub lines: (usize, usize),
+}
+
+/// Finds the definitions named by `query` in `source`.
+///
+/// `query` is a bare name (`build_prompt`) or qualified by enclosing types or modules
+/// (`OracleService::new`, `Parser.parse`); for Go methods the receiver type acts as the
+/// qualifier. Every matching definition is returned in source order.
+pub fn extract_symbol(
+    path: &Path,
+    source: &str,
+    query: &str,
+) -> Result<Vec<SymbolExcerpt>, String> {
+    let lang = Lang::from_path(path).ok_or_else(|| {
+        "symbol references are supported for Rust, TypeScript/JavaScript, Python and Go files"
+            .to_string()
+    })?;
+    let tree = parse(lang, source).ok_or_else(|| "failed to parse file".to_string())?;
+
+    let segments: Vec<&str> = query
+        .split("::")
+        .flat_map(|segment| segment.split('.'))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let Some((name, qualifiers)) = segments.split_last() else {
+        return Err("empty symbol name".to_string());
+    };
+
+    let mut matches = Vec::new();
+    visit_definitions(lang, tree.root_node(), &mut |node| {
+        if definition_name(node, source) != Some(*name) {
+            return;
+        }
+        let scope = enclosing_names(lang, node, source);
+        if scope.len() >= qualifiers.len() && scope.ends_with(qualifiers) {
+            matches.push(render_definition(lang, node, source));
+        }
+    });
+
+    if matches.is_empty() {
+        return Err(format!("no definition named '{query}' found"));
+    }
+    Ok(matches)
+}
+
+/// Signatures of the top-level definitions in `source`, with the members of top-level
+/// impl/class/trait/module blocks indented one level below their header.
+pub fn outline(lang: Lang, source: &str) -> Vec<String> {
+    let Some(tree) = parse(lang, source) else {
+        return Vec::new();
+    };
+    let mut lines = Vec::new();
+    collect_outline(lang, tree.root_node(), source, 0, &mut lines);
+    lines
+}
+
+fn collect_outline(
+    lang: Lang,
+    node: Node<'_>,
+    source: &str,
+    depth: usize,
+    lines: &mut Vec<String>,
+) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let kind = child.kind();
+        if lang.is_definition(kind) || lang.is_container(kind) {
+            lines.push(format!(
+                "{}{}",
+                "  ".repeat(depth),
+                signature(lang, child, source)
+            ));
+            if depth == 0
+                && lang.is_container(kind)
+                && let Some(body) = child.child_by_field_name("body")
+            {
+                collect_outline(lang, body, source, depth + 1, lines);
+            }
+        } else if lang.is_wrapper(kind) {
+            collect_outline(lang, child, source, depth, lines);
+        }
+    }
+}
+
+const MAX_SIGNATURE_CHARS: usize = 160;
+
+/// A definition's header (everything before its body) on a single line.
+fn signature(lang: Lang, node: Node<'_>, source: &str) -> String {
+    // Python decorators stay out of the outline; `export`/`const` wrappers belong to the signature.
+    let start = if lang == Lang::Python {
+        node.start_byte()
+    } else {
+        outer_node(lang, node).start_byte()
+    };
+    let end = node
+        .child_by_field_name("body")
+        .map_or(node.end_byte(), |body| body.start_byte());
+
+    let collapsed = source[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(", )", ")")
+        .replace(",)", ")");
+    if collapsed.chars().count() <= MAX_SIGNATURE_CHARS {
+        return collapsed;
+    }
+    let mut truncated: String = collapsed.chars().take(MAX_SIGNATURE_CHARS).collect();
+    truncated.push_str(" …");
+    truncated
+}
+
+fn parse(lang: Lang, source: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(&lang.grammar()).ok()?;
+    parser.parse(source, None)
+}
+
+fn visit_definitions<'t>(lang: Lang, node: Node<'t>, visit: &mut impl FnMut(Node<'t>)) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        if lang.is_definition(child.kind()) {
+            visit(child);
+        }
+        visit_definitions(lang, child, visit);
+    }
+}
+
+fn definition_name<'s>(node: Node<'_>, source: &'s str) -> Option<&'s str> {
+    let name = node.child_by_field_name("name")?;
+    // Destructured bindings (`const { a, b } = ...`) have no single name.
+    if name.kind().contains("pattern") {
+        return None;
+    }
+    name.utf8_text(source.as_bytes()).ok()
+}
+
+/// Names of the containers enclosing `node`, outermost first.
+fn enclosing_names<'s>(lang: Lang, node: Node<'_>, source: &'s str) -> Vec<&'s str> {
+    let mut names = Vec::new();
+
+    if lang == Lang::Go
+        && node.kind() == "method_declaration"
+        && let Some(receiver) = node.child_by_field_name("receiver")
+        && let Some(type_name) = find_descendant(receiver, "type_identifier")
+        && let Ok(text) = type_name.utf8_text(source.as_bytes())
+    {
+        names.push(text);
+    }
+
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if lang.is_container(parent.kind())
+            && let Some(name) = container_name(parent, source)
+        {
+            names.push(name);
+        }
+        current = parent.parent();
+    }
+
+    names.reverse();
+    names
+}
+
+fn container_name<'s>(node: Node<'_>, source: &'s str) -> Option<&'s str> {
+    let name = if node.kind() == "impl_item" {
+        node.child_by_field_name("type")?
+    } else {
+        node.child_by_field_name("name")?
+    };
+    let text = name.utf8_text(source.as_bytes()).ok()?;
+    // `impl<T> Foo<T>` is addressed as `Foo`.
+    Some(text.split('<').next().unwrap_or(text).trim())
+}
+
+fn find_descendant<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+    if node.kind() == kind {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .find_map(|child| find_descendant(child, kind))
+}
+
+/// The node spanning a definition together with wrappers such as `export` or decorators.
+fn outer_node<'t>(lang: Lang, node: Node<'t>) -> Node<'t> {
+    let mut outer = node;
+    while let Some(parent) = outer.parent() {
+        if !lang.is_wrapper(parent.kind()) {
+            break;
+        }
+        outer = parent;
+    }
+    outer
+}
+
+/// Start byte of the doc comments and attributes directly above `node`.
+fn leading_comment_start(lang: Lang, node: Node<'_>) -> usize {
+    let mut start = node.start_byte();
+    let mut row = node.start_position().row;
+    let mut current = node.prev_named_sibling();
+    while let Some(sibling) = current {
+        if !lang.is_leading_comment(sibling.kind()) || sibling.end_position().row + 1 < row {
+            break;
+        }
+        start = sibling.start_byte();
+        row = sibling.start_position().row;
+        current = sibling.prev_named_sibling();
+    }
+    start
+}
+
+fn line_start(source: &str, byte: usize) -> usize {
+    source[..byte].rfind('\n').map_or(0, |idx| idx + 1)
+}
+
+fn line_end(source: &str, byte: usize) -> usize {
+    source[byte..]
+        .find('\n')
+        .map_or(source.len(), |idx| byte + idx)
+}
+
+/// Leading whitespace of the line containing `byte`.
+fn indentation(source: &str, byte: usize) -> &str {
+    let line = &source[line_start(source, byte)..];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn render_definition(lang: Lang, node: Node<'_>, source: &str) -> SymbolExcerpt {
+    let outer = outer_node(lang, node);
+    let start = line_start(source, leading_comment_start(lang, outer));
+    let end = line_end(source, outer.end_byte());
+    let item = &source[start..end];
+
+    let first_line = source[..start].matches('\n').count() + 1;
+    let last_line = first_line + item.matches('\n').count();
+
+    let mut containers = Vec::new();
+    let mut current = outer.parent();
+    while let Some(parent) = current {
+        if lang.is_container(parent.kind()) {
+            containers.push(parent);
+        }
+        current = parent.parent();
+    }
+    containers.reverse();
+
+    // Elision markers sit at the indentation of whatever is nested directly inside each container.
+    let inner_indents: Vec<&str> = containers
+        .iter()
+        .skip(1)
+        .map(|container| indentation(source, container.start_byte()))
+        .chain([indentation(source, outer.start_byte())])
+        .collect();
+
+    let mut text = String::new();
+    for (container, inner) in containers.iter().zip(&inner_indents) {
+        let header_start = line_start(source, container.start_byte());
+        let header_end = container
+            .child_by_field_name("body")
+            .map_or(container.end_byte(), |body| body.start_byte());
+        text.push_str(source[header_start..header_end].trim_end());
+        if lang.uses_braces() {
+            text.push_str(" {");
+        }
+        text.push('\n');
+        text.push_str(&format!("{inner}{}\n", lang.elision()));
+    }
+
+    text.push_str(item);
+    text.push('\n');
+
+    for (container, inner) in containers.iter().zip(&inner_indents).rev() {
+        text.push_str(&format!("{inner}{}\n", lang.elision()));
+        if lang.uses_braces() {
+            let indent = indentation(source, container.start_byte());
+            text.push_str(&format!("{indent}}}\n"));
+        }
+    }
+
+    SymbolExcerpt {
+        text,
+        lines: (first_line, last_line),
+    }
+}
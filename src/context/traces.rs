@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+use ignore::WalkBuilder;
+use regex::Regex;
+
+use super::expand::{ContextFile, FileOrigin};
+use super::spec::Selection;
+
+/// Lines of context shown on each side of a frame.
+pub const FRAME_WINDOW_LINES: usize = 15;
+/// At most this many distinct frames are resolved per request.
+const MAX_FRAMES: usize = 12;
+/// Cap on the workspace file list used to resolve frames by path suffix.
+const MAX_INDEXED_FILES: usize = 50_000;
+
+/// `File "app/views.py", line 42, in handler`
+static PYTHON_FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"File "([^"]+)", line (\d+)"#).expect("valid regex"));
+
+/// `at com.acme.Service.run(Service.java:42)`, optionally with a module prefix (`java.base/`)
+static JVM_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"at (?:[\w.$-]+(?:@[\w.-]+)?/)?((?:[\w$]+\.)*)[\w$]+\.[\w$<>]+\(([\w$]+\.(?:java|kt|scala|groovy)):(\d+)\)",
+    )
+    .expect("valid regex")
+});
+
+/// `src/main.rs:10:5`, `/app/index.js:12:3`, `file:///app/x.ts:3`, `main.go:42 +0x1d`
+static PATH_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:file://)?((?:[A-Za-z]:)?[\w./\\@+~-]*[\w-]\.[A-Za-z][A-Za-z0-9]{0,9}):(\d+)")
+        .expect("valid regex")
+});
+
+/// Path fragments of frames in the standard library, toolchains and third-party packages.
+const EXTERNAL_MARKERS: &[&str] = &[
+    "/rustc/",
+    "/.cargo/registry/",
+    "/.cargo/git/",
+    "/.rustup/",
+    "library/std/",
+    "library/core/",
+    "library/alloc/",
+    "site-packages/",
+    "dist-packages/",
+    "/lib/python",
+    "<frozen ",
+    "node_modules/",
+    "node:internal",
+    "/usr/lib/go",
+    "/usr/local/go/",
+    "/go/pkg/mod/",
+];
+
+/// JVM packages that belong to the runtime rather than the project.
+const EXTERNAL_JVM_PACKAGES: &[&str] = &["java.", "javax.", "jdk.", "sun.", "kotlin.", "scala."];
+
+/// A `file:line` reference found in pasted text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub path: String,
+    pub line: usize,
+}
+
+/// Extracts stack frames from panics, backtraces, tracebacks and stack traces in `text`, in the
+/// order they appear, skipping standard-library and dependency frames.
+pub fn find_frames(text: &str) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut claimed = Vec::new();
+
+    for caps in PYTHON_FRAME.captures_iter(text) {
+        let whole = caps.get(0).expect("match");
+        claimed.push(whole.range());
+        push_frame(&mut frames, whole.start(), &caps[1], &caps[2]);
+    }
+
+    for caps in JVM_FRAME.captures_iter(text) {
+        let whole = caps.get(0).expect("match");
+        claimed.push(whole.range());
+        let package = &caps[1];
+        if EXTERNAL_JVM_PACKAGES
+            .iter()
+            .any(|prefix| package.starts_with(prefix))
+        {
+            continue;
+        }
+        let path = format!("{}{}", package.replace('.', "/"), &caps[2]);
+        push_frame(&mut frames, whole.start(), &path, &caps[3]);
+    }
+
+    for caps in PATH_LINE.captures_iter(text) {
+        let whole = caps.get(0).expect("match");
+        if claimed.iter().any(|range| range.contains(&whole.start())) {
+            continue;
+        }
+        push_frame(&mut frames, whole.start(), &caps[1], &caps[2]);
+    }
+
+    frames.sort_by_key(|(offset, _)| *offset);
+    let mut seen = HashSet::new();
+    frames
+        .into_iter()
+        .map(|(_, frame)| frame)
+        .filter(|frame| seen.insert(frame.clone()))
+        .collect()
+}
+
+fn push_frame(frames: &mut Vec<(usize, Frame)>, offset: usize, path: &str, line: &str) {
+    let normalized = path.replace('\\', "/");
+    if EXTERNAL_MARKERS
+        .iter()
+        .any(|marker| normalized.contains(marker))
+    {
+        return;
+    }
+    let Ok(line) = line.parse::<usize>() else {
+        return;
+    };
+    if line == 0 {
+        return;
+    }
+    frames.push((
+        offset,
+        Frame {
+            path: normalized,
+            line,
+        },
+    ));
+}
+
+/// Resolves frames against the workspace under `base` and turns them into windowed excerpts
+/// tagged with `origin`.
+///
+/// A frame path that does not exist as given (absolute paths from CI machines, Java package
+/// paths) is matched by its longest suffix that names a workspace file. Frames in the same file
+/// whose windows overlap are merged. Files in `skip` (already included whole) are left alone.
+/// Returns the excerpts plus the frames that could not be resolved.
+pub fn frame_excerpts(
+    frames: &[Frame],
+    base: &Path,
+    skip: &HashSet<PathBuf>,
+    origin: FileOrigin,
+) -> (Vec<ContextFile>, Vec<Frame>) {
+    let mut workspace: Option<Vec<PathBuf>> = None;
+    let mut windows: Vec<(String, usize, usize)> = Vec::new();
+    let mut unresolved = Vec::new();
+
+    for frame in frames.iter().take(MAX_FRAMES) {
+        let resolved = resolve_direct(&frame.path, base).or_else(|| {
+            let files = workspace.get_or_insert_with(|| index_workspace(base));
+            resolve_by_suffix(&frame.path, files)
+        });
+        let Some(display) = resolved else {
+            unresolved.push(frame.clone());
+            continue;
+        };
+        if skip.contains(&base.join(&display)) {
+            continue;
+        }
+
+        let start = frame.line.saturating_sub(FRAME_WINDOW_LINES).max(1);
+        let end = frame.line + FRAME_WINDOW_LINES;
+        match windows
+            .iter_mut()
+            .find(|(path, s, e)| *path == display && start <= *e + 1 && end + 1 >= *s)
+        {
+            Some(window) => {
+                window.1 = window.1.min(start);
+                window.2 = window.2.max(end);
+            }
+            None => windows.push((display, start, end)),
+        }
+    }
+
+    let files = windows
+        .into_iter()
+        .map(|(display, start, end)| ContextFile {
+            path: base.join(&display),
+            display,
+            selection: Selection::Lines { start, end },
+            origin,
+            revision: None,
+            member: None,
+        })
+        .collect();
+
+    (files, unresolved)
+}
+
+/// The frame path relative to `base` if it names a file there directly.
+fn resolve_direct(path: &str, base: &Path) -> Option<String> {
+    let candidate = Path::new(path);
+    if candidate.is_absolute() {
+        let base = base.canonicalize().ok()?;
+        let relative = candidate.strip_prefix(&base).ok()?;
+        return base
+            .join(relative)
+            .is_file()
+            .then(|| relative.to_string_lossy().into_owned());
+    }
+
+    let relative: PathBuf = candidate
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    base.join(&relative)
+        .is_file()
+        .then(|| relative.to_string_lossy().into_owned())
+}
+
+/// The workspace file sharing the longest path suffix with `path`. A bare file-name match is
+/// only accepted when it is unambiguous.
+fn resolve_by_suffix(path: &str, files: &[PathBuf]) -> Option<String> {
+    let components: Vec<&str> = path
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+
+    (1..=components.len()).rev().find_map(|len| {
+        let suffix: PathBuf = components[components.len() - len..].iter().collect();
+        let mut matches = files.iter().filter(|file| file.ends_with(&suffix));
+        let first = matches.next()?;
+        if len == 1 && matches.next().is_some() {
+            return None;
+        }
+        Some(first.to_string_lossy().into_owned())
+    })
+}
+
+fn index_workspace(base: &Path) -> Vec<PathBuf> {
+    WalkBuilder::new(base)
+        .require_git(false)
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .build()
+        .filter_map(Result::ok)
+        .filter(|dent| dent.file_type().is_some_and(|kind| kind.is_file()))
+        .take(MAX_INDEXED_FILES)
+        .filter_map(|dent| dent.path().strip_prefix(base).ok().map(Path::to_path_buf))
+        .collect()
+}
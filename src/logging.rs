@@ -0,0 +1,57 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+const LOG_ENV_VAR: &str = "ORACLE_LOG";
+const DEFAULT_LOG_FILTER: &str = "warn";
+
+/// Installs the global tracing subscriber.
+///
+/// Logs never go to stdout because stdout carries the stdio MCP channel (and the CLI answer).
+/// They are written to `log_file` when given, otherwise to stderr. The filter comes from
+/// `level` (`--log-level`), then `ORACLE_LOG`, then defaults to `warn`; any `EnvFilter`
+/// directive such as `oracle=debug,rmcp=info` is accepted.
+pub fn init(
+    level: Option<&str>,
+    log_file: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let filter = match level {
+        Some(level) => EnvFilter::try_new(level)
+            .map_err(|err| format!("Invalid --log-level '{level}': {err}"))?,
+        None => match EnvFilter::try_from_env(LOG_ENV_VAR) {
+            Ok(filter) => filter,
+            Err(_) => EnvFilter::new(DEFAULT_LOG_FILTER),
+        },
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(true);
+
+    match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| format!("Failed to open log file {}: {err}", path.display()))?;
+            builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .try_init()
+                .map_err(|err| err.to_string())?;
+        }
+        None => {
+            builder
+                .with_writer(std::io::stderr)
+                .try_init()
+                .map_err(|err| err.to_string())?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,17 +1,28 @@
 mod cli;
+mod config;
+mod context;
+mod logging;
+mod notify;
+mod redact;
+mod rest;
 mod service;
+mod tokens;
 
 use clap::Parser;
 use cli::{Cli, Command, run_cli_call};
+use rest::run_rest_server;
 use service::run_server;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    logging::init(cli.log_level.as_deref(), cli.log_file.as_deref())?;
+    let config = config::load()?;
 
     match cli.command {
-        Some(Command::Call(args)) => run_cli_call(args).await?,
-        _ => run_server().await?,
+        Some(Command::Call(args)) => run_cli_call(*args, config).await?,
+        Some(Command::Serve(args)) if args.rest => run_rest_server(args.bind, config).await?,
+        _ => run_server(config).await?,
     }
 
     Ok(())
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use rmcp::model::{LoggingLevel, LoggingMessageNotificationParam};
+use rmcp::{Peer, RoleServer};
+use serde_json::{Map, Value};
+
+/// Minimum level the MCP client asked for via `logging/setLevel`, shared across requests.
+pub type SharedLogLevel = Arc<Mutex<LoggingLevel>>;
+
+pub const DEFAULT_CLIENT_LOG_LEVEL: LoggingLevel = LoggingLevel::Info;
+
+/// Reports notable events while handling a request.
+///
+/// Every event is written to `tracing`. When the request came from an MCP client it is also
+/// sent as a `notifications/message` so clients can show why an answer looks off without
+/// anyone attaching to stderr. CLI and REST callers use the default, tracing-only notifier.
+#[derive(Clone, Default)]
+pub struct ClientNotifier {
+    client: Option<(Peer<RoleServer>, SharedLogLevel)>,
+}
+
+impl ClientNotifier {
+    pub fn mcp(peer: Peer<RoleServer>, level: SharedLogLevel) -> Self {
+        Self {
+            client: Some((peer, level)),
+        }
+    }
+
+    pub async fn info(&self, logger: &str, message: &str, fields: Value) {
+        self.emit(LoggingLevel::Info, logger, message, fields).await;
+    }
+
+    pub async fn notice(&self, logger: &str, message: &str, fields: Value) {
+        self.emit(LoggingLevel::Notice, logger, message, fields)
+            .await;
+    }
+
+    pub async fn warning(&self, logger: &str, message: &str, fields: Value) {
+        self.emit(LoggingLevel::Warning, logger, message, fields)
+            .await;
+    }
+
+    async fn emit(&self, level: LoggingLevel, logger: &str, message: &str, fields: Value) {
+        match level {
+            LoggingLevel::Debug => tracing::debug!(logger, %fields, "{message}"),
+            LoggingLevel::Info | LoggingLevel::Notice => {
+                tracing::info!(logger, %fields, "{message}")
+            }
+            LoggingLevel::Warning => tracing::warn!(logger, %fields, "{message}"),
+            _ => tracing::error!(logger, %fields, "{message}"),
+        }
+
+        let Some((peer, min_level)) = &self.client else {
+            return;
+        };
+
+        let min_level = *min_level.lock().unwrap_or_else(|err| err.into_inner());
+        if severity(level) < severity(min_level) {
+            return;
+        }
+
+        let mut data = match fields {
+            Value::Object(map) => map,
+            Value::Null => Map::new(),
+            other => Map::from_iter([("details".to_string(), other)]),
+        };
+        data.insert("message".to_string(), Value::String(message.to_string()));
+
+        let param = LoggingMessageNotificationParam {
+            level,
+            logger: Some(logger.to_string()),
+            data: Value::Object(data),
+        };
+
+        if let Err(err) = peer.notify_logging_message(param).await {
+            tracing::debug!(error = %err, "failed to forward log message to MCP client");
+        }
+    }
+}
+
+fn severity(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
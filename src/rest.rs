@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+use tokio::task::AbortHandle;
+
+use crate::config::Config;
+use crate::notify::ClientNotifier;
+use crate::service::{OracleRequest, OracleService};
+
+/// How long a finished job's result stays available for polling.
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Most finished jobs kept at once; the oldest are dropped first.
+const MAX_FINISHED_JOBS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct JobSnapshot {
+    id: String,
+    status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    answer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+struct Job {
+    snapshot: JobSnapshot,
+    abort: Option<AbortHandle>,
+    /// When the job completed or failed; `None` while it is running.
+    finished: Option<Instant>,
+}
+
+/// Drops finished jobs older than [`FINISHED_JOB_TTL`], then the oldest ones beyond
+/// [`MAX_FINISHED_JOBS`]. Running jobs are never dropped.
+fn evict_finished(jobs: &mut HashMap<String, Job>) {
+    let now = Instant::now();
+    jobs.retain(|_, job| {
+        job.finished
+            .is_none_or(|finished| now.duration_since(finished) < FINISHED_JOB_TTL)
+    });
+
+    let mut finished: Vec<(Instant, String)> = jobs
+        .iter()
+        .filter_map(|(id, job)| job.finished.map(|at| (at, id.clone())))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort_unstable();
+    let excess = finished.len() - MAX_FINISHED_JOBS;
+    for (_, id) in finished.into_iter().take(excess) {
+        jobs.remove(&id);
+    }
+}
+
+#[derive(Clone)]
+struct RestState {
+    service: OracleService,
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+    id_prefix: u64,
+    next_id: Arc<AtomicU64>,
+}
+
+impl RestState {
+    fn new(config: Config) -> Self {
+        let id_prefix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            service: OracleService::new(config),
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            id_prefix,
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    fn allocate_id(&self) -> String {
+        let n = self.next_id.fetch_add(1, Ordering::Relaxed);
+        format!("job_{:x}{n:06x}", self.id_prefix)
+    }
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn job_not_found(id: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("No job with id '{id}'"))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+pub async fn run_rest_server(
+    addr: SocketAddr,
+    config: Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let app = Router::new()
+        .route("/v1/solve", post(solve))
+        .route("/v1/jobs/{id}", get(get_job).delete(delete_job))
+        .route("/openapi.json", get(openapi))
+        .with_state(RestState::new(config));
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    eprintln!(
+        "Oracle REST API listening on http://{}",
+        listener.local_addr()?
+    );
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn solve(
+    State(state): State<RestState>,
+    Json(request): Json<OracleRequest>,
+) -> Result<(StatusCode, Json<JobSnapshot>), ApiError> {
+    if request.problem.trim().is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Problem text cannot be empty",
+        ));
+    }
+
+    let id = state.allocate_id();
+    let snapshot = JobSnapshot {
+        id: id.clone(),
+        status: JobStatus::Running,
+        answer: None,
+        error: None,
+    };
+
+    // Hold the lock across the spawn so the task cannot finish before the job is registered.
+    let mut jobs = state.jobs.lock().await;
+    evict_finished(&mut jobs);
+
+    let task_state = state.clone();
+    let task_id = id.clone();
+    let handle = tokio::spawn(async move {
+        let result = task_state
+            .service
+            .call_openai(request, &[], &ClientNotifier::default())
+            .await;
+        let mut jobs = task_state.jobs.lock().await;
+        if let Some(job) = jobs.get_mut(&task_id) {
+            match result {
+                Ok(answer) => {
+                    job.snapshot.status = JobStatus::Completed;
+                    job.snapshot.answer = Some(answer);
+                }
+                Err(err) => {
+                    job.snapshot.status = JobStatus::Failed;
+                    job.snapshot.error = Some(err.message.into_owned());
+                }
+            }
+            job.abort = None;
+            job.finished = Some(Instant::now());
+        }
+        evict_finished(&mut jobs);
+    });
+
+    jobs.insert(
+        id,
+        Job {
+            snapshot: snapshot.clone(),
+            abort: Some(handle.abort_handle()),
+            finished: None,
+        },
+    );
+
+    Ok((StatusCode::ACCEPTED, Json(snapshot)))
+}
+
+async fn get_job(
+    State(state): State<RestState>,
+    Path(id): Path<String>,
+) -> Result<Json<JobSnapshot>, ApiError> {
+    let jobs = state.jobs.lock().await;
+    jobs.get(&id)
+        .map(|job| Json(job.snapshot.clone()))
+        .ok_or_else(|| ApiError::job_not_found(&id))
+}
+
+async fn delete_job(
+    State(state): State<RestState>,
+    Path(id): Path<String>,
+) -> Result<Json<JobSnapshot>, ApiError> {
+    let mut jobs = state.jobs.lock().await;
+    let mut job = jobs
+        .remove(&id)
+        .ok_or_else(|| ApiError::job_not_found(&id))?;
+
+    if let Some(abort) = job.abort.take() {
+        abort.abort();
+        job.snapshot.status = JobStatus::Cancelled;
+    }
+
+    Ok(Json(job.snapshot))
+}
+
+async fn openapi() -> Json<Value> {
+    Json(openapi_document())
+}
+
+fn openapi_document() -> Value {
+    let mut request_schema = serde_json::to_value(schemars::schema_for!(OracleRequest))
+        .unwrap_or_else(|_| json!({ "type": "object" }));
+    if let Some(object) = request_schema.as_object_mut() {
+        object.remove("$schema");
+    }
+
+    let job_ref = json!({ "$ref": "#/components/schemas/Job" });
+    let error_ref = json!({ "$ref": "#/components/schemas/Error" });
+    let job_id_param = json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" }
+    });
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Oracle REST API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Submit coding problems to Oracle and poll for the answer without speaking MCP."
+        },
+        "paths": {
+            "/v1/solve": {
+                "post": {
+                    "summary": "Start an Oracle job",
+                    "operationId": "solve",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": "#/components/schemas/OracleRequest" }
+                            }
+                        }
+                    },
+                    "responses": {
+                        "202": {
+                            "description": "Job accepted and running",
+                            "content": { "application/json": { "schema": job_ref } }
+                        },
+                        "400": {
+                            "description": "Invalid request",
+                            "content": { "application/json": { "schema": error_ref } }
+                        }
+                    }
+                }
+            },
+            "/v1/jobs/{id}": {
+                "get": {
+                    "summary": "Fetch the current state of a job",
+                    "operationId": "getJob",
+                    "parameters": [job_id_param],
+                    "responses": {
+                        "200": {
+                            "description": "Current job state",
+                            "content": { "application/json": { "schema": job_ref } }
+                        },
+                        "404": {
+                            "description": "Unknown job id",
+                            "content": { "application/json": { "schema": error_ref } }
+                        }
+                    }
+                },
+                "delete": {
+                    "summary": "Cancel a running job and discard it",
+                    "operationId": "deleteJob",
+                    "parameters": [job_id_param],
+                    "responses": {
+                        "200": {
+                            "description": "Final job state before removal",
+                            "content": { "application/json": { "schema": job_ref } }
+                        },
+                        "404": {
+                            "description": "Unknown job id",
+                            "content": { "application/json": { "schema": error_ref } }
+                        }
+                    }
+                }
+            }
+        },
+        "components": {
+            "schemas": {
+                "OracleRequest": request_schema,
+                "Job": {
+                    "type": "object",
+                    "required": ["id", "status"],
+                    "properties": {
+                        "id": { "type": "string" },
+                        "status": {
+                            "type": "string",
+                            "enum": ["running", "completed", "failed", "cancelled"]
+                        },
+                        "answer": {
+                            "type": "string",
+                            "description": "Oracle's answer once the job has completed."
+                        },
+                        "error": {
+                            "type": "string",
+                            "description": "Error message if the job failed."
+                        }
+                    }
+                },
+                "Error": {
+                    "type": "object",
+                    "required": ["error"],
+                    "properties": {
+                        "error": { "type": "string" }
+                    }
+                }
+            }
+        }
+    })
+}
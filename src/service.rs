@@ -1,41 +1,176 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::path::Path;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use reqwest::Client;
-use rmcp::handler::server::{tool::ToolRouter, wrapper::Parameters};
-use rmcp::model::{CallToolResult, Content, ErrorData as McpError, ServerCapabilities, ServerInfo};
+use reqwest::{Client, multipart};
 use rmcp::ServiceExt;
-use rmcp::{tool, tool_handler, tool_router};
+use rmcp::handler::server::{tool::ToolRouter, wrapper::Parameters};
+use rmcp::model::{
+    CallToolResult, Content, ErrorData as McpError, ServerCapabilities, ServerInfo,
+    SetLevelRequestParam,
+};
+use rmcp::service::{NotificationContext, RequestContext};
+use rmcp::{Peer, RoleServer, tool, tool_handler, tool_router};
 use schemars::JsonSchema;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use serde_json::json;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use tokio::time::sleep;
+use tracing::field::Empty;
+use url::Url;
 
-const MAX_PROMPT_CHARS: usize = 1_000_000;
+use crate::config::Config;
+use crate::context::archive::{MAX_MEMBER_BYTES, read_member, read_members};
+use crate::context::auto::{AutoSelection, select_relevant_files};
+use crate::context::budget::{PendingBlock, fit_blocks, split_chunks, split_overflow};
+use crate::context::diagnostics::{parse_diagnostics, render_diagnostics};
+use crate::context::documents::{
+    DocumentInput, DocumentKind, MAX_DOCUMENT_BYTES, MAX_DOCUMENTS, PDF_PAGE_TOKENS, extract_text,
+    is_markdown_path, pdf_page_count, render_document_list,
+};
+use crate::context::exec::{render_command_output, run_command};
+use crate::context::expand::{ContextFile, Expansion, FileOrigin, OmittedEntry, expand_entries};
+use crate::context::git::{
+    DiffSource, MAX_CHANGED_FILES, collect_changes, render_changes, show_file,
+};
+use crate::context::images::{
+    IMAGE_TOKENS, ImageInput, MAX_IMAGE_BYTES, MAX_IMAGES, encode_image, is_image_path,
+    render_image_list,
+};
+use crate::context::load::{Encoding, Loaded, LoadedText, MAX_FILE_BYTES, load_bytes, load_file};
+use crate::context::notebook::{MAX_NOTEBOOK_BYTES, is_notebook_path, render_notebook};
+use crate::context::repo_map::build_repo_map;
+use crate::context::roots::Roots;
+use crate::context::sandbox::Sandbox;
+use crate::context::spec::{FileSpec, Selection};
+use crate::context::traces::{find_frames, frame_excerpts};
+use crate::context::{floor_char_boundary, render_file_block};
+use crate::notify::{ClientNotifier, DEFAULT_CLIENT_LOG_LEVEL, SharedLogLevel};
+use crate::redact::{Redactions, Redactor};
+use crate::tokens::Tokenizer;
+
+const ORACLE_INSTRUCTIONS: &str = "You are Oracle, a meticulous, senior-level coding assistant. Always think step-by-step and consider edge cases before answering. When relevant, suggest concrete code changes and explain why.";
 const OPENAI_POLL_TIMEOUT_SECS: u64 = 120;
 const OPENAI_POLL_START_DELAY_MS: u64 = 500;
 const OPENAI_POLL_MAX_DELAY_MS: u64 = 5_000;
 const OPENAI_JSON_PREVIEW_CHARS: usize = 2_000;
+const SUMMARY_INSTRUCTIONS: &str = "You condense source files for a senior engineer who will answer the coding problem below without seeing the full file. Keep what bears on the problem exact: signatures, types, constants, error messages and the code of the most relevant functions, with line numbers where the file shows them. Describe the rest in a sentence or two. Never invent code that is not in the file.";
+/// A file longer than this many summary-model prompts is summarized only in part.
+const MAX_SUMMARY_PARTS: usize = 8;
+/// Below this much room for file content per summary request, summarizing is not attempted.
+const MIN_SUMMARY_CHUNK_TOKENS: usize = 1_024;
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct OracleRequest {
     /// Natural-language description of the coding problem you want help with.
     pub problem: String,
-    /// List of file paths to include as context. Paths are resolved relative to the working dir.
+    /// List of file paths, directories or glob patterns (e.g. `src/**/*.rs`) to include as context.
+    /// Append `:START-END` or `:LINE±N` to a file path to include only those lines, or `#Name`
+    /// (`#Type::method`) to include one definition with its doc comments (Rust, TS/JS, Python, Go).
+    /// Put `@REV` right after a file path (`src/lib.rs@v0.1.0`, `src/lib.rs@HEAD~3:10-40`) to read
+    /// it from git history instead of the working tree.
+    /// Name a member of a `.zip`, `.tar`, `.tar.gz` or `.crate` archive with `!/`
+    /// (`vendor/serde.crate!/src/lib.rs`, or a glob or directory inside it).
+    /// Prefix a pattern with `!` to exclude matches. Paths are resolved relative to the working dir,
+    /// or to a root from `roots` when prefixed with its name (`client:src/api.ts`); directories and
+    /// globs skip files ignored by `.gitignore`.
     pub files: Option<Vec<String>>,
     /// Optional extra context or notes.
     pub extra_context: Option<String>,
+    /// Prepend a compact outline of the repository (directory tree and top-level signatures) so the
+    /// model can see how the named files fit into the rest of the project.
+    pub repo_map: Option<bool>,
+    /// Index the workspace locally (BM25 over identifiers and paths) and add the files most relevant
+    /// to the problem and extra context. The answer lists which files were picked and why.
+    pub auto_context: Option<bool>,
+    /// Machine-readable compiler or linter output: `cargo build --message-format=json` lines, an
+    /// ESLint `--format json` report or `tsc` output. The findings become a structured Diagnostics
+    /// section and the code they point at is included as excerpts.
+    pub diagnostics: Option<String>,
+    /// Commands to run in the working directory before asking (e.g. `cargo test -p core`). Each
+    /// one's exit code and the tail of its stdout and stderr are added to the prompt. Commands are
+    /// split like a shell would but not run through one, and must match the server's `[exec] allow`
+    /// list in the config file.
+    pub commands: Option<Vec<String>>,
+    /// Add the diff of uncommitted changes (staged and unstaged) against `HEAD`, plus the last few
+    /// commits.
+    pub git_diff: Option<bool>,
+    /// Add the diff of changes staged in the index.
+    pub git_staged: Option<bool>,
+    /// Add the diff and commit log of a range such as `main..HEAD` (a single revision means
+    /// `REV..HEAD`).
+    pub git_range: Option<String>,
+    /// With `git_diff`, `git_staged` or `git_range`, also include the full post-change contents of
+    /// the touched files (up to 20).
+    pub include_changed_files: Option<bool>,
+    /// When the project files don't fit the prompt, have a cheaper model (`[summarize] model` in the
+    /// config file) condense the lowest-priority ones into summaries focused on the problem, instead
+    /// of shortening or dropping them. Costs one extra request per summarized file.
+    pub summarize_overflow: Option<bool>,
+    /// Screenshots or diagrams to show the model: PNG, JPEG, WebP or GIF paths, globs or
+    /// `path@REV`. They are attached as images rather than text, and so are image files named in
+    /// `files`.
+    pub images: Option<Vec<String>>,
+    /// Specs, RFCs or datasheets to include: PDF, Word (`.docx`) or Markdown paths, globs or
+    /// `path@REV`. PDFs are uploaded and attached as files unless the config file turns that off,
+    /// in which case their text is extracted locally like Word documents'; Markdown is included as
+    /// text. PDF and Word files named in `files` are handled the same way.
+    pub documents: Option<Vec<String>>,
+    /// Named workspace roots, such as `{"client": "../web", "server": "."}`, for looking at several
+    /// repositories side by side. Entries in `files`, `images` and `documents` prefixed with
+    /// `NAME:` resolve against that root. Relative roots resolve against the working dir, or the
+    /// MCP client's first workspace root, and must stay inside the client's roots.
+    pub roots: Option<HashMap<String, String>>,
+}
+
+impl OracleRequest {
+    /// The git changes the request asks for, in a fixed order.
+    fn diff_sources(&self) -> Vec<DiffSource> {
+        let mut sources = Vec::new();
+        if self.git_diff == Some(true) {
+            sources.push(DiffSource::WorkingTree);
+        }
+        if self.git_staged == Some(true) {
+            sources.push(DiffSource::Staged);
+        }
+        if let Some(range) = &self.git_range {
+            sources.push(DiffSource::Range(range.clone()));
+        }
+        sources
+    }
 }
 
 #[derive(Clone)]
 pub struct OracleService {
     tool_router: ToolRouter<OracleService>,
     http: Client,
+    client_log_level: SharedLogLevel,
+    config: Arc<Config>,
+    /// Skip the `[exec] allow` check; set for commands the local user passes to `oracle call`.
+    allow_any_command: bool,
+    /// Let the request's named roots widen the sandbox; set for `oracle call --root`.
+    trust_named_roots: bool,
+    /// Send the prompt without redacting secrets; set by `oracle call --no-redact`.
+    skip_redaction: bool,
+    /// Workspace roots from the MCP client's `roots/list`, fetched on first use and dropped when
+    /// the client sends `notifications/roots/list_changed`.
+    client_roots: Arc<Mutex<ClientRoots>>,
+}
+
+/// The cached `roots/list` result. `generation` counts `roots/list_changed` notifications, so a
+/// listing that was in flight when the roots changed is not cached.
+#[derive(Debug, Default)]
+struct ClientRoots {
+    generation: u64,
+    listed: Option<Vec<PathBuf>>,
 }
 
 impl OracleService {
-    pub fn new() -> Self {
+    pub fn new(config: Config) -> Self {
         let http = Client::builder()
             .user_agent("oracle-mcp-server/0.1")
             .build()
@@ -44,7 +179,171 @@ impl OracleService {
         Self {
             tool_router: Self::tool_router(),
             http,
+            client_log_level: Arc::new(Mutex::new(DEFAULT_CLIENT_LOG_LEVEL)),
+            config: Arc::new(config),
+            allow_any_command: false,
+            trust_named_roots: false,
+            skip_redaction: false,
+            client_roots: Arc::default(),
+        }
+    }
+
+    /// Runs any `commands` in a request without consulting the allow-list.
+    pub fn allow_any_command(mut self) -> Self {
+        self.allow_any_command = true;
+        self
+    }
+
+    /// Lets the request's named roots point anywhere, making them readable.
+    pub fn trust_named_roots(mut self) -> Self {
+        self.trust_named_roots = true;
+        self
+    }
+
+    /// Sends prompts as they are, whatever the `[redact]` config says.
+    pub fn skip_redaction(mut self) -> Self {
+        self.skip_redaction = true;
+        self
+    }
+
+    /// The redaction rules from the config file, or `None` when redaction is off.
+    fn redactor(&self) -> Result<Option<Redactor>, McpError> {
+        if self.skip_redaction || !self.config.redact.enabled {
+            return Ok(None);
+        }
+        Redactor::new(&self.config.redact)
+            .map(Some)
+            .map_err(|err| McpError::internal_error(err, None))
+    }
+
+    /// Rejects `commands` that cannot be parsed or that the allow-list does not cover.
+    fn check_commands(&self, request: &OracleRequest) -> Result<(), McpError> {
+        for command in request.commands.iter().flatten() {
+            let argv = match shlex::split(command) {
+                Some(argv) if !argv.is_empty() => argv,
+                _ => {
+                    return Err(McpError::invalid_params(
+                        format!("Could not parse command `{command}`"),
+                        None,
+                    ));
+                }
+            };
+            if !self.allow_any_command && !self.config.exec.allows(&argv) {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "Command `{command}` is not allowed; add it to `[exec] allow` in the Oracle config file"
+                    ),
+                    None,
+                ));
+            }
         }
+        Ok(())
+    }
+
+    /// The roots `files` entries resolve against: the client's workspace roots (the working
+    /// directory when there are none) plus the request's named roots, with the sandbox from the
+    /// config file.
+    async fn request_roots(
+        &self,
+        request: &OracleRequest,
+        workspace: &[PathBuf],
+    ) -> Result<Roots, McpError> {
+        let workspace = workspace.to_vec();
+        let mut readable = if workspace.is_empty() {
+            vec![PathBuf::from(".")]
+        } else {
+            workspace.clone()
+        };
+        readable.extend(self.config.sandbox.allowed_dirs());
+        let deny = self.config.sandbox.deny.clone();
+        let named = request.roots.clone();
+        let trusted = self.trust_named_roots;
+        tokio::task::spawn_blocking(move || {
+            let sandbox = Sandbox::new(&readable, &deny)
+                .map_err(|err| McpError::internal_error(err, None))?;
+            let roots = Roots::new(&workspace, sandbox);
+            match &named {
+                Some(named) => roots
+                    .with_named(named, trusted)
+                    .map_err(|err| McpError::invalid_params(err, None)),
+                None => Ok(roots),
+            }
+        })
+        .await
+        .map_err(|_| McpError::internal_error("Failed to resolve the workspace roots", None))?
+    }
+
+    /// The MCP client's workspace roots as local directories, or none when the client doesn't
+    /// support roots or listing them fails. Roots that aren't `file://` URIs are skipped.
+    async fn client_roots(
+        &self,
+        peer: &Peer<RoleServer>,
+        notifier: &ClientNotifier,
+    ) -> Vec<PathBuf> {
+        let generation = {
+            let cached = self
+                .client_roots
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            if let Some(roots) = &cached.listed {
+                return roots.clone();
+            }
+            cached.generation
+        };
+        let supported = peer
+            .peer_info()
+            .is_some_and(|info| info.capabilities.roots.is_some());
+        if !supported {
+            return Vec::new();
+        }
+
+        let listed = match peer.list_roots().await {
+            Ok(result) => result.roots,
+            Err(err) => {
+                notifier
+                    .warning(
+                        "oracle.roots",
+                        "Failed to list the client's workspace roots; resolving files against the server's working directory",
+                        json!({ "error": err.to_string() }),
+                    )
+                    .await;
+                return Vec::new();
+            }
+        };
+        let mut roots = Vec::new();
+        for root in listed {
+            match Url::parse(&root.uri)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+            {
+                Some(path) => roots.push(path),
+                None => {
+                    notifier
+                        .warning(
+                            "oracle.roots",
+                            "Skipped a workspace root that is not a local directory",
+                            json!({ "uri": root.uri, "name": root.name }),
+                        )
+                        .await;
+                }
+            }
+        }
+        notifier
+            .info(
+                "oracle.roots",
+                "Listed the client's workspace roots",
+                json!({ "roots": roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>() }),
+            )
+            .await;
+        let mut cached = self
+            .client_roots
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        // Roots listed before a `list_changed` notification serve this call but aren't cached.
+        if cached.generation == generation {
+            cached.listed = Some(roots.clone());
+        }
+        roots
     }
 
     fn test_mode_enabled() -> bool {
@@ -89,83 +388,168 @@ impl OracleService {
             _ => response.push_str("(none)\n"),
         }
 
+        if request.repo_map == Some(true) {
+            response.push_str("\nRepository map: requested\n");
+        }
+        if request.auto_context == Some(true) {
+            response.push_str("Auto context: requested\n");
+        }
+        if let Some(commands) = request
+            .commands
+            .as_ref()
+            .filter(|commands| !commands.is_empty())
+        {
+            response.push_str("Commands to run:\n");
+            for command in commands {
+                response.push_str(&format!("- {command}\n"));
+            }
+        }
+        for source in request.diff_sources() {
+            response.push_str(&format!("Git changes: {}\n", source.title()));
+        }
+        if let Some(diagnostics) = &request.diagnostics {
+            response.push_str(&format!(
+                "Diagnostics: {} bytes provided\n",
+                diagnostics.len()
+            ));
+        }
+        if request.summarize_overflow == Some(true) {
+            response.push_str("Overflow summaries: requested\n");
+        }
+        let images: Vec<&String> = request
+            .files
+            .iter()
+            .flatten()
+            .filter(|entry| is_image_path(Path::new(FileSpec::parse(entry).inner_path())))
+            .chain(request.images.iter().flatten())
+            .collect();
+        if !images.is_empty() {
+            response.push_str("Images:\n");
+            for image in images {
+                response.push_str(&format!("- {image}\n"));
+            }
+        }
+        let documents: Vec<&String> = request
+            .files
+            .iter()
+            .flatten()
+            .filter(|entry| {
+                DocumentKind::from_path(Path::new(FileSpec::parse(entry).inner_path())).is_some()
+            })
+            .chain(request.documents.iter().flatten())
+            .collect();
+        if !documents.is_empty() {
+            response.push_str("Documents:\n");
+            for document in documents {
+                response.push_str(&format!("- {document}\n"));
+            }
+        }
+        if let Some(roots) = request.roots.as_ref().filter(|roots| !roots.is_empty()) {
+            let mut roots: Vec<_> = roots.iter().collect();
+            roots.sort();
+            response.push_str("Roots:\n");
+            for (name, dir) in roots {
+                response.push_str(&format!("- {name}: {dir}\n"));
+            }
+        }
+
         response
     }
 
-    pub async fn call_openai(&self, request: OracleRequest) -> Result<String, McpError> {
+    /// Answers `request`. Files resolve against `workspace`, the MCP client's roots, when it is
+    /// not empty, and against the working directory otherwise.
+    pub async fn call_openai(
+        &self,
+        request: OracleRequest,
+        workspace: &[PathBuf],
+        notifier: &ClientNotifier,
+    ) -> Result<String, McpError> {
+        self.check_commands(&request)?;
+        let roots = self.request_roots(&request, workspace).await?;
+        let redactor = self.redactor()?;
+
         if Self::test_mode_enabled() {
             return Ok(Self::test_mode_response(&request));
         }
 
-        let user_prompt = build_prompt(&request).await;
+        let BuiltPrompt {
+            text: user_prompt,
+            auto_selected,
+            images,
+            documents,
+            redacted,
+            unscanned,
+        } = build_prompt(self, &request, &roots, redactor.as_ref(), notifier).await?;
 
         let api_key = env::var("OPENAI_API_KEY").map_err(|_| {
             McpError::internal_error("Environment variable OPENAI_API_KEY is not set", None)
         })?;
 
-        // Build Responses API request for gpt-5-pro with high reasoning effort.
-        #[derive(serde::Serialize, Clone)]
-        struct Reasoning {
-            effort: String,
-        }
+        let uploaded = self.upload_documents(&api_key, &documents).await?;
 
-        #[derive(serde::Serialize, Clone)]
-        struct ResponseRequest {
-            model: String,
-            input: String,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            instructions: Option<String>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            reasoning: Option<Reasoning>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            max_output_tokens: Option<u32>,
-        }
+        // Images and documents go after the prompt text in a single user message, in the order the
+        // prompt lists them.
+        let input = if images.is_empty() && uploaded.ids.is_empty() {
+            ResponseInput::Text(user_prompt)
+        } else {
+            let mut content = vec![InputContent::Text { text: user_prompt }];
+            content.extend(images.into_iter().map(|image| InputContent::Image {
+                image_url: image.data_url,
+                detail: "auto",
+            }));
+            content.extend(uploaded.ids.iter().map(|file_id| InputContent::File {
+                file_id: file_id.clone(),
+            }));
+            ResponseInput::Messages(vec![InputMessage {
+                role: "user",
+                content,
+            }])
+        };
+
+        let answer = self
+            .request_answer(&api_key, input, &auto_selected, notifier)
+            .await
+            .map(|mut answer| {
+                append_redaction_note(&mut answer, &redacted, &unscanned);
+                answer
+            });
+        uploaded.delete(notifier).await;
+        answer
+    }
 
-        let mut max_output_tokens: u32 = 2048;
+    /// Sends the prompt to the configured model, retrying with more output room when the answer
+    /// runs out of it, and returns the answer text.
+    async fn request_answer(
+        &self,
+        api_key: &str,
+        input: ResponseInput,
+        auto_selected: &[AutoSelection],
+        notifier: &ClientNotifier,
+    ) -> Result<String, McpError> {
+        // Build Responses API request for the configured model with high reasoning effort.
+        let output_ceiling = self.config.budget(&self.config.model).max_output_tokens;
+        let mut max_output_tokens: u32 = 2048.min(output_ceiling);
         let mut attempts = 0u8;
 
         loop {
             attempts += 1;
 
             let body = ResponseRequest {
-                model: "gpt-5-pro".to_string(),
-                input: user_prompt.clone(),
-                instructions: Some(
-                    "You are Oracle, a meticulous, senior-level coding assistant. Always think step-by-step and consider edge cases before answering. When relevant, suggest concrete code changes and explain why.".to_string(),
-                ),
+                model: self.config.model.clone(),
+                input: input.clone(),
+                instructions: Some(ORACLE_INSTRUCTIONS.to_string()),
                 reasoning: Some(Reasoning {
                     effort: "high".to_string(),
                 }),
                 max_output_tokens: Some(max_output_tokens),
             };
 
-            let resp = self
-                .http
-                .post("https://api.openai.com/v1/responses")
-                .bearer_auth(&api_key)
-                .header("Content-Type", "application/json")
-                .json(&body)
-                .send()
-                .await
-                .map_err(|err| {
-                    McpError::internal_error(format!("Failed to call OpenAI API: {err}"), None)
-                })?;
-
-            if !resp.status().is_success() {
-                let status = resp.status();
-                let text = resp.text().await.unwrap_or_default();
-                return Err(McpError::internal_error(
-                    format!("OpenAI API returned non-success status {status}: {text}"),
-                    None,
-                ));
-            }
-
-            let initial_response: Value = resp.json().await.map_err(|err| {
-                McpError::internal_error(format!("Failed to parse OpenAI response: {err}"), None)
-            })?;
+            let initial_response = self
+                .create_response(api_key, &body, attempts, max_output_tokens)
+                .await?;
 
             let completed_response = self
-                .wait_for_openai_completion(initial_response, &api_key)
+                .wait_for_openai_completion(initial_response, api_key)
                 .await?;
 
             let status = response_status(&completed_response).unwrap_or("unknown");
@@ -175,19 +559,39 @@ impl OracleService {
                 if status == "incomplete" {
                     let reason = incomplete_reason(&completed_response)
                         .unwrap_or_else(|| "reason unavailable".to_string());
+                    notifier
+                        .warning(
+                            "oracle.openai",
+                            "OpenAI stopped early; the answer may be truncated",
+                            json!({ "reason": reason, "max_output_tokens": max_output_tokens }),
+                        )
+                        .await;
                     answer.push_str(&format!(
                         "\n\n[oracle warning] OpenAI stopped early ({reason}). The answer may be truncated.",
                     ));
                 }
+                append_auto_context_note(&mut answer, auto_selected);
                 return Ok(answer);
             }
 
             if status == "incomplete"
                 && incomplete_reason(&completed_response).as_deref() == Some("max_output_tokens")
-                && max_output_tokens < 8192
+                && max_output_tokens < output_ceiling
                 && attempts < 3
             {
-                max_output_tokens = (max_output_tokens * 2).min(8192);
+                let previous = max_output_tokens;
+                max_output_tokens = (max_output_tokens * 2).min(output_ceiling);
+                notifier
+                    .notice(
+                        "oracle.openai",
+                        "Response hit max_output_tokens before producing text; retrying with a larger limit",
+                        json!({
+                            "attempt": attempts + 1,
+                            "previous_max_output_tokens": previous,
+                            "max_output_tokens": max_output_tokens,
+                        }),
+                    )
+                    .await;
                 continue;
             }
 
@@ -213,6 +617,241 @@ impl OracleService {
         }
     }
 
+    #[tracing::instrument(
+        name = "openai.create_response",
+        skip_all,
+        fields(
+            attempt = attempt,
+            max_output_tokens = max_output_tokens,
+            http_status = Empty,
+            response_id = Empty,
+            response_status = Empty,
+            bytes = Empty,
+            duration_ms = Empty,
+        )
+    )]
+    async fn create_response<T: Serialize>(
+        &self,
+        api_key: &str,
+        body: &T,
+        attempt: u8,
+        max_output_tokens: u32,
+    ) -> Result<Value, McpError> {
+        let span = tracing::Span::current();
+        let started = Instant::now();
+
+        let resp = self
+            .http
+            .post("https://api.openai.com/v1/responses")
+            .bearer_auth(api_key)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await
+            .map_err(|err| {
+                tracing::warn!(error = %err, "request to OpenAI failed");
+                McpError::internal_error(format!("Failed to call OpenAI API: {err}"), None)
+            })?;
+
+        let status = resp.status();
+        span.record("http_status", status.as_u16());
+
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            span.record("bytes", text.len());
+            span.record("duration_ms", elapsed_ms(started));
+            tracing::warn!(%status, "OpenAI API returned non-success status");
+            return Err(McpError::internal_error(
+                format!("OpenAI API returned non-success status {status}: {text}"),
+                None,
+            ));
+        }
+
+        let bytes = resp.bytes().await.map_err(|err| {
+            McpError::internal_error(format!("Failed to parse OpenAI response: {err}"), None)
+        })?;
+        span.record("bytes", bytes.len());
+        span.record("duration_ms", elapsed_ms(started));
+
+        let value: Value = serde_json::from_slice(&bytes).map_err(|err| {
+            McpError::internal_error(format!("Failed to parse OpenAI response: {err}"), None)
+        })?;
+        record_response_fields(&span, &value);
+
+        Ok(value)
+    }
+
+    /// Uploads PDFs through the Files API for use as `input_file` parts. If one upload fails, the
+    /// files already uploaded are deleted again.
+    async fn upload_documents(
+        &self,
+        api_key: &str,
+        documents: &[DocumentInput],
+    ) -> Result<UploadedFiles, McpError> {
+        let mut uploaded = UploadedFiles {
+            service: self.clone(),
+            api_key: api_key.to_string(),
+            ids: Vec::new(),
+        };
+        for document in documents {
+            match self.upload_file(api_key, document).await {
+                Ok(file_id) => uploaded.ids.push(file_id),
+                Err(err) => {
+                    uploaded.delete(&ClientNotifier::default()).await;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(uploaded)
+    }
+
+    #[tracing::instrument(
+        name = "openai.upload_file",
+        skip_all,
+        fields(
+            file = %document.display,
+            bytes = document.bytes.len(),
+            http_status = Empty,
+            file_id = Empty,
+            duration_ms = Empty,
+        )
+    )]
+    async fn upload_file(
+        &self,
+        api_key: &str,
+        document: &DocumentInput,
+    ) -> Result<String, McpError> {
+        let span = tracing::Span::current();
+        let started = Instant::now();
+
+        let part = multipart::Part::bytes(document.bytes.clone())
+            .file_name(document.filename.clone())
+            .mime_str("application/pdf")
+            .map_err(|err| {
+                McpError::internal_error(
+                    format!("Failed to upload {}: {err}", document.display),
+                    None,
+                )
+            })?;
+        let form = multipart::Form::new()
+            .text("purpose", "user_data")
+            .part("file", part);
+
+        let resp = self
+            .http
+            .post("https://api.openai.com/v1/files")
+            .bearer_auth(api_key)
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|err| {
+                McpError::internal_error(
+                    format!("Failed to upload {}: {err}", document.display),
+                    None,
+                )
+            })?;
+        let status = resp.status();
+        span.record("http_status", status.as_u16());
+        let text = resp.text().await.unwrap_or_default();
+        span.record("duration_ms", elapsed_ms(started));
+        if !status.is_success() {
+            return Err(McpError::internal_error(
+                format!(
+                    "OpenAI Files API returned non-success status {status} for {}: {text}",
+                    document.display
+                ),
+                None,
+            ));
+        }
+
+        let file_id = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|value| value.get("id")?.as_str().map(str::to_string))
+            .ok_or_else(|| {
+                McpError::internal_error(
+                    format!(
+                        "OpenAI Files API response for {} had no file id: {text}",
+                        document.display
+                    ),
+                    None,
+                )
+            })?;
+        span.record("file_id", file_id.as_str());
+        Ok(file_id)
+    }
+
+    /// Deletes uploaded files once the answer is in. Failures are reported but don't affect the
+    /// answer.
+    async fn delete_files(&self, api_key: &str, file_ids: &[String], notifier: &ClientNotifier) {
+        for file_id in file_ids {
+            let result = self
+                .http
+                .delete(format!("https://api.openai.com/v1/files/{file_id}"))
+                .bearer_auth(api_key)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+            if let Err(err) = result {
+                notifier
+                    .warning(
+                        "oracle.documents",
+                        "Failed to delete an uploaded file",
+                        json!({ "file_id": file_id, "error": err.to_string() }),
+                    )
+                    .await;
+            }
+        }
+    }
+
+    #[tracing::instrument(
+        name = "openai.poll",
+        skip_all,
+        fields(
+            response_id = response_id,
+            iteration = iteration,
+            elapsed_ms = elapsed.as_millis() as u64,
+            http_status = Empty,
+            response_status = Empty,
+            bytes = Empty,
+            duration_ms = Empty,
+        )
+    )]
+    async fn poll_response(
+        &self,
+        response_id: &str,
+        api_key: &str,
+        iteration: u32,
+        elapsed: Duration,
+    ) -> Result<Value, McpError> {
+        let span = tracing::Span::current();
+        let started = Instant::now();
+
+        let resp = self
+            .http
+            .get(format!("https://api.openai.com/v1/responses/{response_id}"))
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .map_err(|err| {
+                tracing::warn!(error = %err, "poll request to OpenAI failed");
+                McpError::internal_error(format!("Failed to poll OpenAI response: {err}"), None)
+            })?;
+        span.record("http_status", resp.status().as_u16());
+
+        let bytes = resp.bytes().await.map_err(|err| {
+            McpError::internal_error(format!("Failed to parse OpenAI poll response: {err}"), None)
+        })?;
+        span.record("bytes", bytes.len());
+        span.record("duration_ms", elapsed_ms(started));
+
+        let value: Value = serde_json::from_slice(&bytes).map_err(|err| {
+            McpError::internal_error(format!("Failed to parse OpenAI poll response: {err}"), None)
+        })?;
+        record_response_fields(&span, &value);
+
+        Ok(value)
+    }
+
     async fn wait_for_openai_completion(
         &self,
         mut response_json: Value,
@@ -234,9 +873,11 @@ impl OracleService {
 
         let mut delay = Duration::from_millis(OPENAI_POLL_START_DELAY_MS);
         let mut elapsed = Duration::ZERO;
+        let mut iteration = 0u32;
 
         loop {
             let status = response_status(&response_json).unwrap_or("unknown");
+            tracing::debug!(response_id, status, iteration, "OpenAI response status");
 
             match status {
                 "completed" | "incomplete" => return Ok(response_json),
@@ -268,6 +909,11 @@ impl OracleService {
                 }
                 status if should_poll_status(status) => {
                     if elapsed >= Duration::from_secs(OPENAI_POLL_TIMEOUT_SECS) {
+                        tracing::warn!(
+                            response_id,
+                            elapsed_ms = elapsed.as_millis() as u64,
+                            "timed out waiting for OpenAI response"
+                        );
                         return Err(McpError::internal_error(
                             format!(
                                 "Timed out waiting for OpenAI response {response_id} to finish. Last payload: {}",
@@ -280,27 +926,11 @@ impl OracleService {
                     sleep(delay).await;
                     elapsed += delay;
                     delay = next_poll_delay(delay);
+                    iteration += 1;
 
                     response_json = self
-                        .http
-                        .get(format!("https://api.openai.com/v1/responses/{response_id}"))
-                        .bearer_auth(api_key)
-                        .send()
-                        .await
-                        .map_err(|err| {
-                            McpError::internal_error(
-                                format!("Failed to poll OpenAI response: {err}"),
-                                None,
-                            )
-                        })?
-                        .json()
-                        .await
-                        .map_err(|err| {
-                            McpError::internal_error(
-                                format!("Failed to parse OpenAI poll response: {err}"),
-                                None,
-                            )
-                        })?;
+                        .poll_response(&response_id, api_key, iteration, elapsed)
+                        .await?;
                 }
                 other => {
                     return Err(McpError::internal_error(
@@ -314,43 +944,950 @@ impl OracleService {
             }
         }
     }
+
+    /// Replaces the project files that don't fit `available` tokens with summaries written by
+    /// the `[summarize]` model. Files that can't be summarized are returned unchanged, so the
+    /// budget fit shortens or drops them as usual. Fails only if splitting the blocks does.
+    async fn summarize_overflow(
+        &self,
+        request: &OracleRequest,
+        blocks: Vec<PendingBlock>,
+        available: usize,
+        tokenizer: Tokenizer,
+        notifier: &ClientNotifier,
+    ) -> Result<Vec<PendingBlock>, McpError> {
+        let settings = &self.config.summarize;
+        let (summary_tokens, max_files) = (settings.summary_tokens, settings.max_files);
+        let (mut blocks, overflow) = tokio::task::spawn_blocking(move || {
+            split_overflow(blocks, available, tokenizer, summary_tokens, max_files)
+        })
+        .await
+        .map_err(|err| {
+            McpError::internal_error(
+                format!("Failed to pick the project files to summarize: {err}"),
+                None,
+            )
+        })?;
+        if overflow.is_empty() {
+            return Ok(blocks);
+        }
+
+        let Ok(api_key) = env::var("OPENAI_API_KEY") else {
+            blocks.extend(overflow);
+            return Ok(blocks);
+        };
+
+        let mut question = request.problem.clone();
+        if let Some(extra) = &request.extra_context {
+            question.push('\n');
+            question.push_str(extra);
+        }
+        let question: Arc<str> = question.into();
+        let api_key: Arc<str> = api_key.into();
+        let permits = Arc::new(Semaphore::new(settings.concurrency.max(1)));
+
+        let mut tasks = JoinSet::new();
+        let mut overflow: Vec<Option<PendingBlock>> = overflow.into_iter().map(Some).collect();
+        for (index, slot) in overflow.iter().enumerate().take(max_files) {
+            let Some(block) = slot.clone() else { continue };
+            let (service, question, api_key, permits) = (
+                self.clone(),
+                question.clone(),
+                api_key.clone(),
+                permits.clone(),
+            );
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let result = service.summarize_block(&api_key, &question, &block).await;
+                (index, result)
+            });
+        }
+
+        let mut summarized = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            let Ok((index, result)) = joined else {
+                continue;
+            };
+            let Some(block) = overflow[index].as_ref() else {
+                continue;
+            };
+            match result {
+                Ok(summary) => {
+                    notifier
+                        .info(
+                            "oracle.summarize",
+                            "Summarized a project file that did not fit the prompt",
+                            json!({
+                                "path": block.display,
+                                "model": settings.model,
+                                "tokens": summary.source_tokens,
+                                "summary_tokens": summary.tokens,
+                            }),
+                        )
+                        .await;
+                    summarized.push((index, summary.text));
+                }
+                Err(err) => {
+                    notifier
+                        .warning(
+                            "oracle.summarize",
+                            "Failed to summarize a project file; it will be shortened instead",
+                            json!({ "path": block.display, "error": err.message }),
+                        )
+                        .await;
+                }
+            }
+        }
+
+        summarized.sort_by_key(|(index, _)| *index);
+        for (index, text) in summarized {
+            if let Some(block) = overflow[index].take() {
+                blocks.push(PendingBlock { text, ..block });
+            }
+        }
+        blocks.extend(overflow.into_iter().flatten());
+        Ok(blocks)
+    }
+
+    /// Asks the `[summarize]` model for a summary of one file block, focused on `question`. Files
+    /// too long for the summary model are summarized in parts.
+    async fn summarize_block(
+        &self,
+        api_key: &str,
+        question: &str,
+        block: &PendingBlock,
+    ) -> Result<Summary, McpError> {
+        let model = &self.config.summarize.model;
+        let budget = self.config.budget(model);
+        let tokenizer = budget.tokenizer;
+        let chunk_tokens = budget
+            .input_tokens()
+            .saturating_sub(tokenizer.count(question))
+            .saturating_sub(tokenizer.count(SUMMARY_INSTRUCTIONS))
+            .saturating_sub(256);
+        if chunk_tokens < MIN_SUMMARY_CHUNK_TOKENS {
+            return Err(McpError::invalid_params(
+                format!("The problem text leaves no room for file content in {model}'s context"),
+                None,
+            ));
+        }
+
+        let source_tokens = tokenizer.count(&block.text);
+        let chunks = split_chunks(&block.text, chunk_tokens, tokenizer);
+        let parts = chunks.len().min(MAX_SUMMARY_PARTS);
+        let words_per_part = self.config.summarize.summary_tokens * 3 / 4 / parts;
+        let reasoning = (budget.reasoning_tokens > 0).then(|| Reasoning {
+            effort: "low".to_string(),
+        });
+
+        let mut text = format!(
+            "\n\n===== SUMMARY: {} (condensed by {model} from {source_tokens} tokens; not the file's text) =====\n",
+            block.display
+        );
+        for (part, chunk) in chunks.iter().take(parts).enumerate() {
+            let label = if chunks.len() > 1 {
+                format!(" (part {} of {})", part + 1, chunks.len())
+            } else {
+                String::new()
+            };
+            let body = ResponseRequest {
+                model: model.clone(),
+                input: ResponseInput::Text(format!(
+                    "### Coding problem\n{question}\n\n### File{label}\n{chunk}\n\nSummarize this file in at most {words_per_part} words."
+                )),
+                instructions: Some(SUMMARY_INSTRUCTIONS.to_string()),
+                reasoning: reasoning.clone(),
+                max_output_tokens: Some(budget.max_output_tokens),
+            };
+            let response = self
+                .create_response(api_key, &body, 1, budget.max_output_tokens)
+                .await?;
+            let response = self.wait_for_openai_completion(response, api_key).await?;
+            let summary = extract_output_text(&response).ok_or_else(|| {
+                McpError::internal_error(
+                    format!(
+                        "Summary response did not contain any text output. Raw payload: {}",
+                        summarize_json(&response)
+                    ),
+                    None,
+                )
+            })?;
+            if chunks.len() > 1 {
+                text.push_str(&format!("Part {} of {}:\n", part + 1, chunks.len()));
+            }
+            text.push_str(&summary);
+            text.push('\n');
+        }
+        if chunks.len() > parts {
+            text.push_str(&format!(
+                "...[the last {} of {} parts were not summarized]...\n",
+                chunks.len() - parts,
+                chunks.len()
+            ));
+        }
+
+        Ok(Summary {
+            source_tokens,
+            tokens: tokenizer.count(&text),
+            text,
+        })
+    }
 }
 
-pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
-    let service = OracleService::new();
+pub async fn run_server(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    let service = OracleService::new(config);
     let running = service.serve(rmcp::transport::stdio()).await?;
     let _ = running.waiting().await;
     Ok(())
 }
 
-async fn build_prompt(request: &OracleRequest) -> String {
-    let request = request.clone();
-    let mut context_blocks = String::new();
+#[derive(serde::Serialize, Clone)]
+struct Reasoning {
+    effort: String,
+}
 
-    if let Some(files) = &request.files {
-        for path in files {
-            let path_obj = Path::new(path);
-            let display = path_obj.display();
+/// Body of a Responses API request.
+#[derive(serde::Serialize, Clone)]
+struct ResponseRequest {
+    model: String,
+    input: ResponseInput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<Reasoning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+}
 
-            match tokio::fs::read_to_string(path_obj).await {
-                Ok(contents) => {
-                    context_blocks
-                        .push_str(&format!("\n\n===== FILE: {display} =====\n{contents}\n",));
+/// The `input` of a Responses API request: plain text, or messages made of several parts.
+#[derive(serde::Serialize, Clone)]
+#[serde(untagged)]
+enum ResponseInput {
+    Text(String),
+    Messages(Vec<InputMessage>),
+}
+
+#[derive(serde::Serialize, Clone)]
+struct InputMessage {
+    role: &'static str,
+    content: Vec<InputContent>,
+}
+
+/// One part of an input message; the variant names the part's `type`.
+#[derive(serde::Serialize, Clone)]
+#[serde(tag = "type")]
+enum InputContent {
+    #[serde(rename = "input_text")]
+    Text { text: String },
+    #[serde(rename = "input_image")]
+    Image {
+        image_url: String,
+        detail: &'static str,
+    },
+    #[serde(rename = "input_file")]
+    File { file_id: String },
+}
+
+/// A summary block standing in for a file that did not fit.
+struct Summary {
+    text: String,
+    source_tokens: usize,
+    tokens: usize,
+}
+
+/// Files uploaded for one request. Dropping the guard with files left, as happens when the
+/// request is cancelled or its task aborted, deletes them in the background.
+struct UploadedFiles {
+    service: OracleService,
+    api_key: String,
+    ids: Vec<String>,
+}
+
+impl UploadedFiles {
+    /// Deletes the files one at a time, so any still listed if this is cancelled are left to
+    /// the drop.
+    async fn delete(mut self, notifier: &ClientNotifier) {
+        while let Some(file_id) = self.ids.last() {
+            self.service
+                .delete_files(&self.api_key, std::slice::from_ref(file_id), notifier)
+                .await;
+            self.ids.pop();
+        }
+    }
+}
+
+impl Drop for UploadedFiles {
+    fn drop(&mut self) {
+        if self.ids.is_empty() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let service = self.service.clone();
+        let api_key = std::mem::take(&mut self.api_key);
+        let ids = std::mem::take(&mut self.ids);
+        runtime.spawn(async move {
+            service
+                .delete_files(&api_key, &ids, &ClientNotifier::default())
+                .await;
+        });
+    }
+}
+
+/// The user prompt plus what was decided while building it that the caller reports back.
+struct BuiltPrompt {
+    text: String,
+    auto_selected: Vec<AutoSelection>,
+    images: Vec<ImageInput>,
+    documents: Vec<DocumentInput>,
+    /// Secrets replaced per source, in prompt order.
+    redacted: Vec<(String, usize)>,
+    /// Attachments sent as they are although redaction is on.
+    unscanned: Vec<String>,
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        files = request.files.as_ref().map_or(0, Vec::len),
+        prompt_bytes = Empty,
+        prompt_tokens = Empty,
+        truncated = Empty,
+    )
+)]
+async fn build_prompt(
+    service: &OracleService,
+    request: &OracleRequest,
+    roots: &Roots,
+    redactor: Option<&Redactor>,
+    notifier: &ClientNotifier,
+) -> Result<BuiltPrompt, McpError> {
+    let mut request = request.clone();
+    let config = service.config.as_ref();
+    let exec = &config.exec;
+    let budget = config.budget(&config.model);
+    let tokenizer = budget.tokenizer;
+    let mut omitted_block = String::new();
+    let mut file_blocks = Vec::new();
+
+    let mut expansion = match request.files.clone() {
+        Some(entries) if !entries.is_empty() => {
+            let roots = roots.clone();
+            tokio::task::spawn_blocking(move || expand_entries(&entries, &roots))
+        }
+        .await
+        .map_err(|err| {
+            McpError::internal_error(format!("Failed to expand the requested files: {err}"), None)
+        })?,
+        _ => Expansion::default(),
+    };
+
+    // Image files are attached as `input_image` parts instead of being read as text.
+    let (mut image_files, text_files): (Vec<ContextFile>, Vec<ContextFile>) =
+        std::mem::take(&mut expansion.files)
+            .into_iter()
+            .partition(|file| {
+                file.selection == Selection::Whole && is_image_path(file.inner_path())
+            });
+    expansion.files = text_files;
+    if let Some(entries) = request.images.clone().filter(|entries| !entries.is_empty()) {
+        let named = {
+            let roots = roots.clone();
+            tokio::task::spawn_blocking(move || expand_entries(&entries, &roots))
+        }
+        .await
+        .map_err(|err| {
+            McpError::internal_error(
+                format!("Failed to expand the requested images: {err}"),
+                None,
+            )
+        })?;
+        image_files.extend(named.files);
+        expansion.omitted.extend(named.omitted);
+    }
+
+    // PDF and Word files are uploaded or have their text extracted; Markdown is read as text.
+    let (mut document_files, text_files): (Vec<ContextFile>, Vec<ContextFile>) =
+        std::mem::take(&mut expansion.files)
+            .into_iter()
+            .partition(|file| {
+                file.selection == Selection::Whole
+                    && DocumentKind::from_path(file.inner_path()).is_some()
+            });
+    expansion.files = text_files;
+    if let Some(entries) = request
+        .documents
+        .clone()
+        .filter(|entries| !entries.is_empty())
+    {
+        let named = {
+            let roots = roots.clone();
+            tokio::task::spawn_blocking(move || expand_entries(&entries, &roots))
+        }
+        .await
+        .map_err(|err| {
+            McpError::internal_error(
+                format!("Failed to expand the requested documents: {err}"),
+                None,
+            )
+        })?;
+        for file in named.files {
+            if DocumentKind::from_path(file.inner_path()).is_some() {
+                document_files.push(file);
+            } else if is_markdown_path(file.inner_path()) {
+                expansion.files.push(file);
+            } else {
+                expansion.omitted.push(OmittedEntry {
+                    entry: file.display_name(),
+                    reason: "not a PDF, Word or Markdown document".to_string(),
+                });
+            }
+        }
+        expansion.omitted.extend(named.omitted);
+    }
+
+    // Archive members are read up front so that each archive is decompressed once.
+    let wanted: Vec<(PathBuf, String)> = expansion
+        .files
+        .iter()
+        .chain(&image_files)
+        .chain(&document_files)
+        .filter_map(|file| Some((file.path.clone(), file.member.clone()?)))
+        .collect();
+    let mut members = if wanted.is_empty() {
+        MemberBytes::new()
+    } else {
+        tokio::task::spawn_blocking(move || read_archive_members(&wanted))
+            .await
+            .map_err(|err| {
+                McpError::internal_error(format!("Failed to read the archive members: {err}"), None)
+            })?
+    };
+
+    let images = load_images(&image_files, &mut members, &mut expansion.omitted).await;
+    // Uploaded PDFs would skip redaction, so with redaction on their text is extracted here.
+    let (documents, document_blocks) = load_documents(
+        &document_files,
+        config.documents.upload && redactor.is_none(),
+        &mut members,
+        &mut expansion.omitted,
+    )
+    .await;
+    if !documents.is_empty() || !document_blocks.is_empty() {
+        notifier
+            .info(
+                "oracle.documents",
+                "Prepared document attachments",
+                json!({
+                    "uploaded": documents
+                        .iter()
+                        .map(|document| json!({ "path": document.display, "pages": document.pages }))
+                        .collect::<Vec<_>>(),
+                    "extracted": document_blocks
+                        .iter()
+                        .map(|block| block.display.clone())
+                        .collect::<Vec<_>>(),
+                }),
+            )
+            .await;
+    }
+    if !images.is_empty() {
+        notifier
+            .info(
+                "oracle.images",
+                "Attached images",
+                json!({
+                    "images": images
+                        .iter()
+                        .map(|image| json!({ "path": image.display, "bytes": image.bytes }))
+                        .collect::<Vec<_>>(),
+                }),
+            )
+            .await;
+    }
+
+    let mut request_text = request.problem.clone();
+    if let Some(extra) = &request.extra_context {
+        request_text.push('\n');
+        request_text.push_str(extra);
+    }
+
+    // Commands were validated by `check_commands`; their output is scanned for stack frames too.
+    let mut command_blocks = String::new();
+    for command in request.commands.iter().flatten() {
+        let argv = shlex::split(command).unwrap_or_default();
+        let output = run_command(
+            command,
+            &argv,
+            roots.base(),
+            Duration::from_secs(exec.timeout_secs),
+            exec.max_output_bytes,
+        )
+        .await;
+        notifier
+            .info(
+                "oracle.exec",
+                "Captured command output",
+                json!({
+                    "command": command,
+                    "exit_code": output.exit_code(),
+                    "duration_ms": output.duration.as_millis() as u64,
+                    "stdout_bytes": output.stdout.total,
+                    "stderr_bytes": output.stderr.total,
+                }),
+            )
+            .await;
+        command_blocks.push_str(&render_command_output(&output, exec.max_output_bytes));
+        request_text.push('\n');
+        request_text.push_str(&output.combined_text());
+    }
+
+    let whole_files: HashSet<PathBuf> = expansion
+        .files
+        .iter()
+        .filter(|file| file.selection == Selection::Whole)
+        .map(|file| file.path.clone())
+        .collect();
+
+    let frames = find_frames(&request_text);
+    if !frames.is_empty() {
+        let skip = whole_files.clone();
+        let frame_list = frames.clone();
+        let roots = roots.clone();
+        let (failure_files, unresolved, refused) = tokio::task::spawn_blocking(move || {
+            let (mut files, unresolved) =
+                frame_excerpts(&frame_list, roots.base(), &skip, FileOrigin::FailurePath);
+            let refused = refuse_sandboxed(&mut files, &roots);
+            (files, unresolved, refused)
+        })
+        .await
+        .map_err(|err| {
+            McpError::internal_error(
+                format!("Failed to read the stack-frame excerpts: {err}"),
+                None,
+            )
+        })?;
+        expansion.omitted.extend(refused);
+
+        notifier
+            .info(
+                "oracle.traces",
+                "Resolved stack frames from the problem text",
+                json!({
+                    "frames": frames.len(),
+                    "excerpts": failure_files
+                        .iter()
+                        .map(|file| file.display.clone())
+                        .collect::<Vec<_>>(),
+                    "unresolved": unresolved
+                        .iter()
+                        .map(|frame| format!("{}:{}", frame.path, frame.line))
+                        .collect::<Vec<_>>(),
+                }),
+            )
+            .await;
+
+        expansion.files.extend(failure_files);
+    }
+
+    let mut diagnostics_section = None;
+    if let Some(raw) = request
+        .diagnostics
+        .as_deref()
+        .filter(|raw| !raw.trim().is_empty())
+    {
+        let diagnostics = parse_diagnostics(raw);
+        diagnostics_section = Some(render_diagnostics(&diagnostics, raw));
+
+        // Errors first so the frame cap keeps the spans that matter most.
+        let frames: Vec<_> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == "error")
+            .chain(
+                diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.severity != "error"),
+            )
+            .filter_map(|diagnostic| diagnostic.frame())
+            .collect();
+        let skip = whole_files;
+        let roots = roots.clone();
+        let (excerpts, unresolved, refused) = tokio::task::spawn_blocking(move || {
+            let (mut files, unresolved) =
+                frame_excerpts(&frames, roots.base(), &skip, FileOrigin::Diagnostic);
+            let refused = refuse_sandboxed(&mut files, &roots);
+            (files, unresolved, refused)
+        })
+        .await
+        .map_err(|err| {
+            McpError::internal_error(
+                format!("Failed to read the diagnostic excerpts: {err}"),
+                None,
+            )
+        })?;
+        expansion.omitted.extend(refused);
+
+        if diagnostics.is_empty() {
+            notifier
+                .warning(
+                    "oracle.diagnostics",
+                    "Diagnostics format not recognized; including the raw output",
+                    json!({ "bytes": raw.len() }),
+                )
+                .await;
+        } else {
+            notifier
+                .info(
+                    "oracle.diagnostics",
+                    "Parsed compiler diagnostics",
+                    json!({
+                        "diagnostics": diagnostics.len(),
+                        "excerpts": excerpts
+                            .iter()
+                            .map(|file| file.display.clone())
+                            .collect::<Vec<_>>(),
+                        "unresolved": unresolved
+                            .iter()
+                            .map(|frame| format!("{}:{}", frame.path, frame.line))
+                            .collect::<Vec<_>>(),
+                    }),
+                )
+                .await;
+        }
+
+        expansion.files.extend(excerpts);
+    }
+
+    let mut git_section = String::new();
+    for source in request.diff_sources() {
+        match collect_changes(&source, roots.base(), &roots.sandbox().git_excludes()).await {
+            Ok(changes) => {
+                notifier
+                    .info(
+                        "oracle.git",
+                        "Collected git changes",
+                        json!({
+                            "source": changes.title,
+                            "diff_bytes": changes.diff.len(),
+                            "truncated": changes.diff_truncated,
+                            "changed_files": changes.changed_files.len(),
+                        }),
+                    )
+                    .await;
+                git_section.push_str(&render_changes(&changes));
+
+                if request.include_changed_files == Some(true) {
+                    let revision = source.tip();
+                    let mut new_files: Vec<ContextFile> = changes
+                        .changed_files
+                        .iter()
+                        .map(|display| ContextFile {
+                            display: display.clone(),
+                            path: roots.base().join(display),
+                            selection: Selection::Whole,
+                            origin: FileOrigin::Changed,
+                            revision: revision.clone(),
+                            member: None,
+                        })
+                        .filter(|file| {
+                            !expansion.files.iter().any(|existing| {
+                                existing.path == file.path
+                                    && existing.selection == Selection::Whole
+                                    && existing.revision == file.revision
+                            })
+                        })
+                        .collect();
+                    let checked = roots.clone();
+                    let (mut new_files, refused) = tokio::task::spawn_blocking(move || {
+                        let refused = refuse_sandboxed(&mut new_files, &checked);
+                        (new_files, refused)
+                    })
+                    .await
+                    .map_err(|err| {
+                        McpError::internal_error(
+                            format!("Failed to check the changed files: {err}"),
+                            None,
+                        )
+                    })?;
+                    new_files.truncate(MAX_CHANGED_FILES);
+                    expansion.files.extend(new_files);
+                    expansion.omitted.extend(refused);
                 }
-                Err(err) => {
-                    context_blocks.push_str(&format!(
-                        "\n\n===== FILE: {display} (error reading) =====\n{err}\n",
-                    ));
+            }
+            Err(err) => {
+                notifier
+                    .warning(
+                        "oracle.git",
+                        "Failed to collect git changes",
+                        json!({ "source": source.title(), "error": err }),
+                    )
+                    .await;
+                git_section.push_str(&format!("#### {}\n(failed: {err})\n\n", source.title()));
+            }
+        }
+    }
+
+    let mut auto_selected = Vec::new();
+    if request.auto_context == Some(true) {
+        let query = request_text.clone();
+        let exclude: HashSet<PathBuf> = expansion
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .collect();
+        let roots = roots.clone();
+        let ignored;
+        (auto_selected, ignored) = tokio::task::spawn_blocking(move || {
+            let mut ignored = Vec::new();
+            let selected = select_relevant_files(&roots, &query, &exclude, &mut ignored);
+            (selected, ignored)
+        })
+        .await
+        .map_err(|err| {
+            McpError::internal_error(
+                format!("Failed to pick the auto-context files: {err}"),
+                None,
+            )
+        })?;
+        if !ignored.is_empty() {
+            expansion.omitted.push(OmittedEntry {
+                entry: "auto_context".to_string(),
+                reason: format!(
+                    "did not index {}",
+                    ignored
+                        .iter()
+                        .map(|(path, ignored)| format!("{path}, {ignored}"))
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ),
+            });
+        }
+
+        notifier
+            .info(
+                "oracle.auto_context",
+                "Auto-selected context files",
+                json!({
+                    "files": auto_selected
+                        .iter()
+                        .map(|selection| json!({
+                            "path": selection.display,
+                            "score": selection.score,
+                            "matched_terms": selection.matched_terms,
+                        }))
+                        .collect::<Vec<_>>(),
+                }),
+            )
+            .await;
+
+        expansion
+            .files
+            .extend(auto_selected.iter().map(|selection| ContextFile {
+                display: selection.display.clone(),
+                path: selection.path.clone(),
+                selection: Selection::Whole,
+                origin: FileOrigin::AutoSelected,
+                revision: None,
+                member: None,
+            }));
+    }
+    tracing::Span::current().record("files", expansion.files.len());
+
+    if !expansion.omitted.is_empty() {
+        omitted_block.push_str("\n\n===== OMITTED FROM CONTEXT =====\n");
+        for omitted in &expansion.omitted {
+            notifier
+                .warning(
+                    "oracle.files",
+                    "Context entry omitted",
+                    json!({ "entry": omitted.entry, "reason": omitted.reason }),
+                )
+                .await;
+            omitted_block.push_str(&format!("- {}: {}\n", omitted.entry, omitted.reason));
+        }
+    }
+
+    for file in &expansion.files {
+        let display = &file.display_name();
+
+        let text = match read_context_file(file, &mut members).await {
+            Ok(Loaded::Binary { bytes }) => {
+                notifier
+                    .warning(
+                        "oracle.files",
+                        "Skipped binary context file",
+                        json!({ "path": display, "bytes": bytes }),
+                    )
+                    .await;
+                format!("\n\n===== FILE: {display} (binary file, {bytes} bytes; skipped) =====\n\n")
+            }
+            Ok(Loaded::Text(loaded)) => {
+                if let Some(size) = loaded.truncated_from {
+                    notifier
+                        .warning(
+                            "oracle.files",
+                            "Large context file shortened to its first and last parts",
+                            json!({ "path": display, "bytes": size, "limit": MAX_FILE_BYTES }),
+                        )
+                        .await;
+                }
+                let block = render_file_block(file, &loaded);
+                if let Some(problem) = &block.problem {
+                    notifier
+                        .warning(
+                            "oracle.files",
+                            "Context file selection could not be resolved",
+                            json!({ "path": display, "reason": problem }),
+                        )
+                        .await;
                 }
+                block.text
             }
+            Err(err) => {
+                notifier
+                    .warning(
+                        "oracle.files",
+                        "Failed to read context file",
+                        json!({ "path": display, "error": err.to_string() }),
+                    )
+                    .await;
+                format!("\n\n===== FILE: {display} (error reading) =====\n{err}\n")
+            }
+        };
+        file_blocks.push(PendingBlock {
+            display: display.clone(),
+            priority: file.origin.priority(),
+            text,
+        });
+    }
+    file_blocks.extend(document_blocks);
+
+    let mut repo_map = String::new();
+    if request.repo_map == Some(true) {
+        let roots = roots.clone();
+        repo_map = tokio::task::spawn_blocking(move || build_repo_map(&roots))
+            .await
+            .map_err(|err| {
+                McpError::internal_error(format!("Failed to build the repository map: {err}"), None)
+            })?;
+    }
+
+    // Secrets are replaced before any of this leaves the machine, summaries included. Images
+    // can't be scanned, so they are reported instead.
+    let mut redactions = Redactions::default();
+    let mut unscanned = Vec::new();
+    if let Some(redactor) = redactor.cloned() {
+        let mut sections = vec![
+            ("problem".to_string(), std::mem::take(&mut request.problem)),
+            (
+                "extra context".to_string(),
+                request.extra_context.take().unwrap_or_default(),
+            ),
+            (
+                "diagnostics".to_string(),
+                diagnostics_section.take().unwrap_or_default(),
+            ),
+            ("git changes".to_string(), std::mem::take(&mut git_section)),
+            ("repository map".to_string(), std::mem::take(&mut repo_map)),
+            (
+                "command output".to_string(),
+                std::mem::take(&mut command_blocks),
+            ),
+        ];
+        sections.extend(
+            file_blocks
+                .iter_mut()
+                .map(|block| (block.display.clone(), std::mem::take(&mut block.text))),
+        );
+        let scanned;
+        (scanned, redactions) = tokio::task::spawn_blocking(move || {
+            let mut redactions = Redactions::default();
+            let scanned: Vec<String> = sections
+                .into_iter()
+                .map(|(source, text)| redactor.redact(&text, &source, &mut redactions))
+                .collect();
+            (scanned, redactions)
+        })
+        .await
+        .map_err(|err| McpError::internal_error(format!("Redaction failed: {err}"), None))?;
+
+        let mut scanned = scanned.into_iter();
+        let mut next = || scanned.next().unwrap_or_default();
+        request.problem = next();
+        request.extra_context = Some(next()).filter(|extra| !extra.is_empty());
+        diagnostics_section = Some(next()).filter(|section| !section.is_empty());
+        git_section = next();
+        repo_map = next();
+        command_blocks = next();
+        for block in &mut file_blocks {
+            block.text = next();
         }
+        unscanned = images.iter().map(|image| image.display.clone()).collect();
+    }
+    if !unscanned.is_empty() {
+        notifier
+            .warning(
+                "oracle.redact",
+                "Images are sent without scanning for secrets",
+                json!({ "images": unscanned }),
+            )
+            .await;
+    }
+    if redactions.total() > 0 {
+        notifier
+            .notice(
+                "oracle.redact",
+                "Redacted secrets from the prompt",
+                json!({
+                    "total": redactions.total(),
+                    "sources": redactions
+                        .counts
+                        .iter()
+                        .map(|(source, count)| json!({ "source": source, "count": count }))
+                        .collect::<Vec<_>>(),
+                }),
+            )
+            .await;
     }
 
     let mut user_prompt = String::new();
     user_prompt.push_str("You are Oracle, a senior software engineer MCP tool.\n");
     user_prompt.push_str("You will be given a coding problem and optional project files.\n");
     user_prompt.push_str("Carefully analyze the problem, read the files, reason step-by-step, and produce a clear, actionable answer.\n\n");
-    user_prompt.push_str("Context is constrained to stay under roughly 256k tokens. If you see '[truncated]' markers, some content was cut to fit the budget.\n\n");
+    user_prompt.push_str(&format!(
+        "Context is constrained to stay under roughly {}k tokens. If you see '[truncated]' markers or a 'CUT TO FIT THE PROMPT BUDGET' list, some content was cut to fit the budget.\n\n",
+        budget.input_tokens() / 1000
+    ));
+    if request.summarize_overflow == Some(true) {
+        user_prompt.push_str("Blocks headed 'SUMMARY' stand in for files that did not fit: a smaller model condensed them with this problem in mind, so they paraphrase the code rather than quote all of it.\n\n");
+    }
+
+    if redactions.total() > 0 {
+        user_prompt.push_str("Values that looked like secrets (keys, tokens, passwords) were replaced with placeholders such as `[REDACTED:github-token:1]`; the same placeholder always stands for the same value. Treat them as opaque and don't flag them as bugs.\n\n");
+    }
+
+    let named_roots: Vec<String> = roots
+        .named()
+        .map(|(name, dir)| format!("`{name}:` = {}", dir.display()))
+        .collect();
+    if !named_roots.is_empty() {
+        user_prompt.push_str(&format!(
+            "Files come from several workspace roots; a path starting with `NAME:` is relative to that root ({}), other paths to the main working directory.\n\n",
+            named_roots.join(", ")
+        ));
+    }
+    if roots.workspace().len() > 1 {
+        let workspace: Vec<String> = roots
+            .workspace()
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect();
+        user_prompt.push_str(&format!(
+            "The workspace has several roots ({}); a relative path is relative to the one that contains it.\n\n",
+            workspace.join(", ")
+        ));
+    }
 
     user_prompt.push_str("### Coding problem\n");
     user_prompt.push_str(&request.problem);
@@ -362,29 +1899,427 @@ async fn build_prompt(request: &OracleRequest) -> String {
         user_prompt.push_str("\n\n");
     }
 
-    if !context_blocks.is_empty() {
-        let header = "### Project files\n";
-        let trunc_notice =
-            "\n\n...[truncated project file content to respect ~256k-token context budget]...\n";
+    if let Some(section) = &diagnostics_section {
+        user_prompt.push_str("### Diagnostics\n");
+        user_prompt.push_str(section);
+        user_prompt.push('\n');
+    }
 
-        let base_len = user_prompt.len() + header.len() + trunc_notice.len();
-        let available_for_files = MAX_PROMPT_CHARS.saturating_sub(base_len);
+    if !git_section.is_empty() {
+        user_prompt.push_str("### Git changes\n");
+        user_prompt.push_str(&git_section);
+    }
+
+    if !repo_map.is_empty() {
+        user_prompt.push_str("### Repository map\n");
+        user_prompt.push_str("Directory structure and top-level signatures of the project; full contents of selected files follow.\n```\n");
+        user_prompt.push_str(&repo_map);
+        user_prompt.push_str("```\n\n");
+    }
 
+    if !command_blocks.is_empty() {
+        user_prompt.push_str("### Command output\n");
+        user_prompt.push_str("Commands run in the project directory just before this request.");
+        user_prompt.push_str(&command_blocks);
+        user_prompt.push_str("\n\n");
+    }
+
+    if !images.is_empty() {
+        user_prompt.push_str("### Images\n");
+        user_prompt.push_str(&render_image_list(&images));
+        user_prompt.push('\n');
+    }
+
+    if !documents.is_empty() {
+        user_prompt.push_str("### Documents\n");
+        user_prompt.push_str(&render_document_list(&documents));
+        user_prompt.push('\n');
+    }
+
+    if !omitted_block.is_empty() || !file_blocks.is_empty() {
+        let header = "### Project files\n";
         user_prompt.push_str(header);
+        user_prompt.push_str(&omitted_block);
 
-        if available_for_files == 0 {
-            user_prompt.push_str(trunc_notice);
-        } else if context_blocks.len() as usize > available_for_files {
-            let mut truncated = context_blocks;
-            truncated.truncate(available_for_files);
-            user_prompt.push_str(&truncated);
-            user_prompt.push_str(trunc_notice);
-        } else {
-            user_prompt.push_str(&context_blocks);
+        let prompt_so_far = user_prompt.clone();
+        let image_tokens = images.len() * IMAGE_TOKENS
+            + documents
+                .iter()
+                .map(|document| document.pages * PDF_PAGE_TOKENS)
+                .sum::<usize>();
+        let available_for_files = tokio::task::spawn_blocking(move || {
+            budget
+                .input_tokens()
+                .saturating_sub(tokenizer.count(&prompt_so_far))
+                .saturating_sub(tokenizer.count(ORACLE_INSTRUCTIONS))
+                .saturating_sub(image_tokens)
+        })
+        .await
+        .map_err(|err| {
+            McpError::internal_error(format!("Failed to measure the prompt: {err}"), None)
+        })?;
+
+        if request.summarize_overflow == Some(true) {
+            file_blocks = service
+                .summarize_overflow(
+                    &request,
+                    file_blocks,
+                    available_for_files,
+                    tokenizer,
+                    notifier,
+                )
+                .await?;
+        }
+
+        let (files_text, cuts) = tokio::task::spawn_blocking(move || {
+            fit_blocks(file_blocks, available_for_files, tokenizer)
+        })
+        .await
+        .map_err(|err| {
+            McpError::internal_error(
+                format!("Failed to fit the project files into the prompt: {err}"),
+                None,
+            )
+        })?;
+
+        tracing::Span::current().record("truncated", !cuts.is_empty());
+        if !cuts.is_empty() {
+            notifier
+                .warning(
+                    "oracle.prompt",
+                    "Project files exceed the prompt budget; some were shortened or dropped",
+                    json!({
+                        "available_tokens": available_for_files,
+                        "cuts": cuts
+                            .iter()
+                            .map(|cut| json!({ "path": cut.display, "action": cut.description }))
+                            .collect::<Vec<_>>(),
+                    }),
+                )
+                .await;
+        }
+        user_prompt.push_str(&files_text);
+    }
+
+    tracing::Span::current().record("prompt_bytes", user_prompt.len());
+    if tracing::enabled!(tracing::Level::INFO) {
+        let prompt = user_prompt.clone();
+        if let Ok(tokens) = tokio::task::spawn_blocking(move || tokenizer.count(&prompt)).await {
+            tracing::Span::current().record("prompt_tokens", tokens);
         }
     }
+    Ok(BuiltPrompt {
+        text: user_prompt,
+        auto_selected,
+        images,
+        documents,
+        redacted: redactions.counts,
+        unscanned,
+    })
+}
 
-    user_prompt
+/// Takes the files the sandbox refuses out of `files` and returns them as omitted entries that
+/// name the rule they broke. Blocking.
+fn refuse_sandboxed(files: &mut Vec<ContextFile>, roots: &Roots) -> Vec<OmittedEntry> {
+    let mut refused = Vec::new();
+    files.retain(|file| match roots.check(&file.path) {
+        Ok(()) => true,
+        Err(violation) => {
+            refused.push(OmittedEntry {
+                entry: file.display_name(),
+                reason: violation.to_string(),
+            });
+            false
+        }
+    });
+    refused
+}
+
+fn append_redaction_note(answer: &mut String, redacted: &[(String, usize)], unscanned: &[String]) {
+    if !redacted.is_empty() {
+        let total: usize = redacted.iter().map(|(_, count)| count).sum();
+        let sources: Vec<String> = redacted
+            .iter()
+            .map(|(source, count)| format!("{source} ({count})"))
+            .collect();
+        answer.push_str(&format!(
+            "\n\n[oracle redaction] Replaced {total} secret(s) with placeholders before sending: {}",
+            sources.join(", ")
+        ));
+    }
+    if !unscanned.is_empty() {
+        answer.push_str(&format!(
+            "\n\n[oracle redaction] Sent without scanning for secrets: {}",
+            unscanned.join(", ")
+        ));
+    }
+}
+
+fn append_auto_context_note(answer: &mut String, auto_selected: &[AutoSelection]) {
+    if auto_selected.is_empty() {
+        return;
+    }
+    answer.push_str("\n\n[oracle auto-context] Files added based on the problem text:");
+    for selection in auto_selected {
+        answer.push_str(&format!(
+            "\n- {} (BM25 score {:.2}; matched: {})",
+            selection.display,
+            selection.score,
+            selection.matched_terms.join(", ")
+        ));
+    }
+}
+
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(path = %file.path.display(), revision = file.revision.as_deref(), bytes = Empty)
+)]
+async fn read_context_file(
+    file: &ContextFile,
+    members: &mut MemberBytes,
+) -> Result<Loaded, String> {
+    // Notebooks are rendered as cells; one that doesn't parse is shown as the raw JSON.
+    if is_notebook_path(file.inner_path()) {
+        let bytes = read_bytes(file, MAX_NOTEBOOK_BYTES, members).await?;
+        let rendered = tokio::task::spawn_blocking(move || render_notebook(&bytes))
+            .await
+            .map_err(|err| err.to_string())?;
+        match rendered {
+            Ok(text) => {
+                tracing::Span::current().record("bytes", text.len());
+                return Ok(Loaded::Text(LoadedText {
+                    text,
+                    encoding: Encoding::Utf8,
+                    streamed_lines: None,
+                    truncated_from: None,
+                }));
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, "could not render notebook; reading it as JSON")
+            }
+        }
+    }
+
+    let loaded = match &file.revision {
+        Some(revision) => load_bytes(&show_file(revision, &file.path).await?, &file.selection),
+        None if file.member.is_some() => load_bytes(
+            &read_bytes(file, MAX_MEMBER_BYTES, members).await?,
+            &file.selection,
+        ),
+        None => {
+            let path = file.path.clone();
+            let selection = file.selection.clone();
+            tokio::task::spawn_blocking(move || load_file(&path, &selection))
+                .await
+                .map_err(|err| err.to_string())?
+                .map_err(|err| err.to_string())?
+        }
+    };
+    if let Loaded::Text(text) = &loaded {
+        tracing::Span::current().record("bytes", text.text.len());
+    }
+    Ok(loaded)
+}
+
+/// Reads and encodes the images to attach. Images that can't be attached are recorded in
+/// `omitted` with the reason.
+async fn load_images(
+    files: &[ContextFile],
+    members: &mut MemberBytes,
+    omitted: &mut Vec<OmittedEntry>,
+) -> Vec<ImageInput> {
+    let mut images = Vec::new();
+    let mut seen = HashSet::new();
+    for file in files {
+        if !seen.insert(file.display_name()) {
+            continue;
+        }
+        let display = file.display_name();
+        if images.len() == MAX_IMAGES {
+            omitted.push(OmittedEntry {
+                entry: display,
+                reason: format!("more than {MAX_IMAGES} images"),
+            });
+            continue;
+        }
+
+        let image = read_bytes(file, MAX_IMAGE_BYTES, members)
+            .await
+            .and_then(|bytes| encode_image(&display, &bytes));
+        match image {
+            Ok(image) => images.push(image),
+            Err(reason) => omitted.push(OmittedEntry {
+                entry: display,
+                reason,
+            }),
+        }
+    }
+    images
+}
+
+/// Reads PDF and Word documents. PDFs become uploads when `upload` is set; otherwise, and for
+/// Word documents, the extracted text becomes a file block. Documents that can't be used are
+/// recorded in `omitted` with the reason.
+async fn load_documents(
+    files: &[ContextFile],
+    upload: bool,
+    members: &mut MemberBytes,
+    omitted: &mut Vec<OmittedEntry>,
+) -> (Vec<DocumentInput>, Vec<PendingBlock>) {
+    let mut documents = Vec::new();
+    let mut blocks = Vec::new();
+    let mut seen = HashSet::new();
+    for file in files {
+        let Some(kind) = DocumentKind::from_path(file.inner_path()) else {
+            continue;
+        };
+        if !seen.insert(file.display_name()) {
+            continue;
+        }
+        let display = file.display_name();
+        if documents.len() + blocks.len() == MAX_DOCUMENTS {
+            omitted.push(OmittedEntry {
+                entry: display,
+                reason: format!("more than {MAX_DOCUMENTS} documents"),
+            });
+            continue;
+        }
+
+        let bytes = match read_bytes(file, MAX_DOCUMENT_BYTES, members).await {
+            Ok(bytes) => bytes,
+            Err(reason) => {
+                omitted.push(OmittedEntry {
+                    entry: display,
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        if kind == DocumentKind::Pdf && upload {
+            let (pages, bytes) =
+                tokio::task::spawn_blocking(move || (pdf_page_count(&bytes), bytes))
+                    .await
+                    .unwrap_or_else(|err| (Err(err.to_string()), Vec::new()));
+            match pages {
+                Ok(pages) => documents.push(DocumentInput {
+                    filename: file
+                        .inner_path()
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| "document.pdf".to_string()),
+                    display,
+                    pages,
+                    bytes,
+                }),
+                Err(reason) => omitted.push(OmittedEntry {
+                    entry: display,
+                    reason,
+                }),
+            }
+            continue;
+        }
+
+        // A panicking extractor surfaces as a join error rather than taking the request down.
+        let text = tokio::task::spawn_blocking(move || extract_text(kind, &bytes))
+            .await
+            .unwrap_or_else(|err| Err(format!("text extraction failed: {err}")));
+        match text {
+            Ok(text) => blocks.push(PendingBlock {
+                text: format!(
+                    "\n\n===== FILE: {display} (text extracted from {}) =====\n{text}\n",
+                    kind.name()
+                ),
+                display,
+                priority: file.origin.priority(),
+            }),
+            Err(reason) => omitted.push(OmittedEntry {
+                entry: display,
+                reason,
+            }),
+        }
+    }
+    (documents, blocks)
+}
+
+/// Archive members read ahead of loading, keyed by archive path and member name.
+type MemberBytes = HashMap<(PathBuf, String), Result<Vec<u8>, String>>;
+
+/// Reads the `(archive, member)` pairs with one pass over each archive. Blocking.
+fn read_archive_members(wanted: &[(PathBuf, String)]) -> MemberBytes {
+    let mut by_archive: HashMap<&Path, HashSet<String>> = HashMap::new();
+    for (archive, member) in wanted {
+        by_archive
+            .entry(archive.as_path())
+            .or_default()
+            .insert(member.clone());
+    }
+    let mut read = MemberBytes::new();
+    for (archive, names) in by_archive {
+        match read_members(archive, &names, MAX_MEMBER_BYTES) {
+            Ok(bytes) => read.extend(
+                bytes
+                    .into_iter()
+                    .map(|(member, bytes)| ((archive.to_path_buf(), member), bytes)),
+            ),
+            Err(reason) => read.extend(
+                names
+                    .into_iter()
+                    .map(|member| ((archive.to_path_buf(), member), Err(reason.clone()))),
+            ),
+        }
+    }
+    read
+}
+
+/// Reads a whole file, from the working tree, git history or an archive, refusing anything over
+/// `limit` bytes. Archive members come from `members` when they were read ahead.
+async fn read_bytes(
+    file: &ContextFile,
+    limit: u64,
+    members: &mut MemberBytes,
+) -> Result<Vec<u8>, String> {
+    let too_large = |size: u64| format!("file is {size} bytes, over the {limit}-byte limit");
+    let bytes = match (&file.revision, &file.member) {
+        (Some(revision), _) => show_file(revision, &file.path).await?,
+        (None, Some(member)) => match members.remove(&(file.path.clone(), member.clone())) {
+            Some(bytes) => bytes?,
+            None => {
+                let (path, member) = (file.path.clone(), member.clone());
+                tokio::task::spawn_blocking(move || read_member(&path, &member, limit))
+                    .await
+                    .map_err(|err| err.to_string())??
+            }
+        },
+        (None, None) => {
+            if let Ok(metadata) = tokio::fs::metadata(&file.path).await
+                && metadata.len() > limit
+            {
+                return Err(too_large(metadata.len()));
+            }
+            tokio::fs::read(&file.path)
+                .await
+                .map_err(|err| err.to_string())?
+        }
+    };
+    if bytes.len() as u64 > limit {
+        return Err(too_large(bytes.len() as u64));
+    }
+    Ok(bytes)
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+fn record_response_fields(span: &tracing::Span, value: &Value) {
+    if let Some(id) = value.get("id").and_then(|v| v.as_str()) {
+        span.record("response_id", id);
+    }
+    if let Some(status) = response_status(value) {
+        span.record("response_status", status);
+    }
 }
 
 fn response_status(value: &Value) -> Option<&str> {
@@ -490,10 +2425,11 @@ fn summarize_json(value: &Value) -> String {
         return json_str;
     }
 
+    let end = floor_char_boundary(&json_str, OPENAI_JSON_PREVIEW_CHARS);
     format!(
-        "{}...[truncated {} chars]",
-        &json_str[..OPENAI_JSON_PREVIEW_CHARS],
-        json_str.len() - OPENAI_JSON_PREVIEW_CHARS
+        "{}...[truncated {} bytes]",
+        &json_str[..end],
+        json_str.len() - end
     )
 }
 
@@ -510,9 +2446,12 @@ impl OracleService {
     )]
     async fn oracle(
         &self,
+        peer: Peer<RoleServer>,
         Parameters(request): Parameters<OracleRequest>,
     ) -> Result<CallToolResult, McpError> {
-        match self.call_openai(request).await {
+        let notifier = ClientNotifier::mcp(peer.clone(), self.client_log_level.clone());
+        let workspace = self.client_roots(&peer, &notifier).await;
+        match self.call_openai(request, &workspace, &notifier).await {
             Ok(answer) => Ok(CallToolResult::success(vec![Content::text(answer)])),
             Err(err) => Ok(CallToolResult::error(vec![Content::text(format!(
                 "Oracle encountered an error: {}",
@@ -526,11 +2465,37 @@ impl OracleService {
 impl rmcp::ServerHandler for OracleService {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_logging()
+                .build(),
             instructions: Some(
                 "Oracle is a coding-focused MCP server that uses OpenAI's gpt-5-pro model with high reasoning to answer questions about your code. Use the `solve_coding_problem` tool with a coding problem and optional file paths; it will analyze the problem and files and propose concrete fixes.".into(),
             ),
             ..Default::default()
         }
     }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        tracing::debug!(level = ?request.level, "MCP client set logging level");
+        *self
+            .client_log_level
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = request.level;
+        Ok(())
+    }
+
+    async fn on_roots_list_changed(&self, _context: NotificationContext<RoleServer>) {
+        tracing::debug!("MCP client changed its workspace roots");
+        let mut cached = self
+            .client_roots
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        cached.generation += 1;
+        cached.listed = None;
+    }
 }
@@ -0,0 +1,57 @@
+//! Counting prompt tokens with the tokenizer of the model that will read them.
+
+use serde::Deserialize;
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton};
+
+/// Tokenizer family used to measure prompts for a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tokenizer {
+    /// `o200k_base`: GPT-4o, GPT-4.1, GPT-5 and the o-series reasoning models.
+    O200k,
+    /// `cl100k_base`: GPT-4 and GPT-3.5.
+    Cl100k,
+    /// Estimate for models without a local tokenizer: about four bytes per token for ASCII and
+    /// one token per character elsewhere, which keeps CJK text from being undercounted.
+    Approx,
+}
+
+impl Tokenizer {
+    /// The tokenizer for a model name, falling back to [`Tokenizer::Approx`] for unknown families.
+    pub fn for_model(model: &str) -> Self {
+        const O200K_PREFIXES: &[&str] =
+            &["gpt-5", "gpt-4o", "gpt-4.1", "gpt-4.5", "o1", "o3", "o4"];
+        const CL100K_PREFIXES: &[&str] = &["gpt-4", "gpt-3.5"];
+
+        if O200K_PREFIXES
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+        {
+            Tokenizer::O200k
+        } else if CL100K_PREFIXES
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+        {
+            Tokenizer::Cl100k
+        } else {
+            Tokenizer::Approx
+        }
+    }
+
+    pub fn count(self, text: &str) -> usize {
+        match self {
+            Tokenizer::O200k => o200k_base_singleton().encode_ordinary(text).len(),
+            Tokenizer::Cl100k => cl100k_base_singleton().encode_ordinary(text).len(),
+            Tokenizer::Approx => {
+                let (ascii, other) = text.chars().fold((0usize, 0usize), |(ascii, other), c| {
+                    if c.is_ascii() {
+                        (ascii + 1, other)
+                    } else {
+                        (ascii, other + 1)
+                    }
+                });
+                ascii.div_ceil(4) + other
+            }
+        }
+    }
+}